@@ -0,0 +1,34 @@
+extern crate hpke_rs as hpke;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use hpke::prelude::*;
+
+fn seal(c: &mut Criterion) {
+    let hpke = Hpke::new(
+        HpkeMode::Base,
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::AesGcm128,
+    );
+    let shared_secret = [0x42u8; 32];
+    let msg = [0x24u8; 64];
+
+    let mut context = hpke
+        .key_schedule(&shared_secret, b"info", &[], &[])
+        .unwrap();
+    c.bench_function("Context::seal (dynamic AEAD)", |b| {
+        b.iter(|| context.seal(black_box(b"aad"), black_box(&msg)).unwrap())
+    });
+
+    let mut context = TypedContext::<HpkeAesGcm128>::new(
+        hpke.key_schedule(&shared_secret, b"info", &[], &[])
+            .unwrap(),
+    )
+    .unwrap();
+    c.bench_function("TypedContext::seal (static AEAD)", |b| {
+        b.iter(|| context.seal(black_box(b"aad"), black_box(&msg)).unwrap())
+    });
+}
+
+criterion_group!(benches, seal);
+criterion_main!(benches);
@@ -1,9 +1,11 @@
 #[cfg(feature = "serialization")]
 pub(crate) use serde::{Deserialize, Serialize};
 
-use crate::aead_impl::*;
+pub use crate::aead_impl::{AesGcm128, AesGcm256, ChaCha20Poly1305};
+use crate::util;
 
 use std::fmt::Debug;
+use zeroize::Zeroize;
 
 /// AEAD modes.
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -38,6 +40,10 @@ impl std::convert::TryFrom<u16> for Mode {
     }
 }
 
+/// The AEAD modes implemented by this build.
+pub(crate) const SUPPORTED_MODES: [Mode; 3] =
+    [Mode::AesGcm128, Mode::AesGcm256, Mode::ChaCha20Poly1305];
+
 /// AEAD Errors
 #[derive(Debug)]
 #[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
@@ -53,12 +59,28 @@ pub enum Error {
 
     /// Unknown AEAD mode
     UnknownMode,
+
+    /// The sequence number is exhausted
+    MessageLimitReached,
 }
 
-pub(crate) trait AeadTrait: Debug + Sync {
+pub(crate) mod sealed {
+    /// Only the AEADs in this crate implement `AeadTrait`.
+    pub trait Sealed {}
+}
+
+/// The interface of the AEAD implementations.
+///
+/// This trait is sealed. It is implemented by `AesGcm128`, `AesGcm256`, and
+/// `ChaCha20Poly1305` and can be used as type parameter of `TypedContext`.
+pub trait AeadTrait: Debug + Send + Sync + sealed::Sealed {
+    /// Create a new AEAD instance.
     fn new() -> Self
     where
         Self: Sized;
+    /// Get the AEAD mode.
+    fn get_mode(&self) -> Mode;
+    /// Seal `plain_txt` with `key`, `nonce`, and `aad`.
     fn seal(
         &self,
         key: &[u8],
@@ -66,6 +88,10 @@ pub(crate) trait AeadTrait: Debug + Sync {
         aad: &[u8],
         plain_txt: &[u8],
     ) -> Result<Vec<u8>, Error>;
+    /// Open `cipher_txt` with `key`, `nonce`, and `aad`.
+    ///
+    /// The tag is checked in constant time. On failure an `OpenError` is
+    /// returned and no plain text is released.
     fn open(
         &self,
         key: &[u8],
@@ -73,8 +99,54 @@ pub(crate) trait AeadTrait: Debug + Sync {
         aad: &[u8],
         cipher_txt: &[u8],
     ) -> Result<Vec<u8>, Error>;
+    /// Seal `plain_txt` like `seal` and return the ciphertext and the tag
+    /// separately.
+    fn seal_detached(
+        &self,
+        key: &[u8],
+        nonce: &[u8],
+        aad: &[u8],
+        plain_txt: &[u8],
+    ) -> Result<(Vec<u8>, Vec<u8>), Error>;
+    /// Open `cipher_txt` with the separate authentication `tag`.
+    fn open_detached(
+        &self,
+        key: &[u8],
+        nonce: &[u8],
+        aad: &[u8],
+        cipher_txt: &[u8],
+        tag: &[u8],
+    ) -> Result<Vec<u8>, Error>;
+    /// Seal with the aad given in parts.
+    /// The parts are concatenated because none of the backends accept the aad
+    /// in parts.
+    fn seal_incremental(
+        &self,
+        key: &[u8],
+        nonce: &[u8],
+        aad_parts: &[&[u8]],
+        plain_txt: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        self.seal(key, nonce, &aad_parts.concat(), plain_txt)
+    }
+    /// Open with the aad given in parts.
+    /// The parts are concatenated because none of the backends accept the aad
+    /// in parts.
+    fn open_incremental(
+        &self,
+        key: &[u8],
+        nonce: &[u8],
+        aad_parts: &[&[u8]],
+        cipher_txt: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        self.open(key, nonce, &aad_parts.concat(), cipher_txt)
+    }
+    /// Get the key length `Nk`.
     fn get_key_length(&self) -> usize;
+    /// Get the nonce length `Nn`.
     fn get_nonce_length(&self) -> usize;
+    /// Get the tag length `Nt`.
+    fn get_tag_length(&self) -> usize;
 }
 
 #[derive(Debug)]
@@ -125,6 +197,9 @@ impl Aead {
     pub fn get_nn(&self) -> usize {
         self.aead.get_nonce_length()
     }
+    pub fn get_nt(&self) -> usize {
+        self.aead.get_tag_length()
+    }
     pub fn seal(
         &self,
         key: &[u8],
@@ -143,4 +218,163 @@ impl Aead {
     ) -> Result<Vec<u8>, Error> {
         self.aead.open(key, nonce, aad, cipher_txt)
     }
+    pub fn seal_detached(
+        &self,
+        key: &[u8],
+        nonce: &[u8],
+        aad: &[u8],
+        plain_txt: &[u8],
+    ) -> Result<(Vec<u8>, Vec<u8>), Error> {
+        self.aead.seal_detached(key, nonce, aad, plain_txt)
+    }
+    pub fn open_detached(
+        &self,
+        key: &[u8],
+        nonce: &[u8],
+        aad: &[u8],
+        cipher_txt: &[u8],
+        tag: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        self.aead.open_detached(key, nonce, aad, cipher_txt, tag)
+    }
+    pub fn seal_incremental(
+        &self,
+        key: &[u8],
+        nonce: &[u8],
+        aad_parts: &[&[u8]],
+        plain_txt: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        self.aead.seal_incremental(key, nonce, aad_parts, plain_txt)
+    }
+    pub fn open_incremental(
+        &self,
+        key: &[u8],
+        nonce: &[u8],
+        aad_parts: &[&[u8]],
+        cipher_txt: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        self.aead
+            .open_incremental(key, nonce, aad_parts, cipher_txt)
+    }
+}
+
+/// Compute the nonce for sequence number `seq` by XORing the big-endian
+/// encoded sequence number with `base_nonce`.
+pub(crate) fn compute_nonce(base_nonce: &[u8], seq: u32) -> Vec<u8> {
+    util::xor_bytes(&encode_seq(seq, base_nonce.len()), base_nonce)
+}
+
+/// Encode the sequence number `seq` as `len` byte big-endian integer, i.e.
+/// `I2OSP(seq, len)`.
+pub(crate) fn encode_seq(seq: u32, len: usize) -> Vec<u8> {
+    let seq = seq.to_be_bytes();
+    let mut enc_seq = vec![0u8; len - seq.len()];
+    enc_seq.extend_from_slice(&seq);
+    enc_seq
+}
+
+/// A sequence-numbered AEAD context with a caller provided key and base
+/// nonce.
+///
+/// This works like the HPKE `Context` with the same nonce derivation, but
+/// without the KEM and the key schedule.
+/// The key and base nonce are zeroized on drop.
+pub struct SymmetricContext {
+    aead: Aead,
+    key: Vec<u8>,
+    base_nonce: Vec<u8>,
+    sequence_number: u32,
+}
+
+impl Debug for SymmetricContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "SymmetricContext {{\n  mode: {:?}\n  seq no: {:?}\n}}",
+            self.aead.mode, self.sequence_number
+        )
+    }
+}
+
+impl SymmetricContext {
+    /// Create a new context for the AEAD `mode` with the given `key` and
+    /// `base_nonce`.
+    ///
+    /// Returns an `InvalidConfig` error if the key length is not `Nk` and an
+    /// `InvalidNonce` error if the base nonce length is not `Nn`.
+    pub fn new(mode: Mode, key: &[u8], base_nonce: &[u8]) -> Result<Self, Error> {
+        let aead = Aead::new(mode);
+        if key.len() != aead.get_nk() {
+            return Err(Error::InvalidConfig);
+        }
+        if base_nonce.len() != aead.get_nn() {
+            return Err(Error::InvalidNonce);
+        }
+        Ok(Self {
+            aead,
+            key: key.to_vec(),
+            base_nonce: base_nonce.to_vec(),
+            sequence_number: 0,
+        })
+    }
+
+    /// Seal `plain_txt` with `aad` and the nonce for the current sequence
+    /// number, and increment the sequence number.
+    ///
+    /// Returns a `MessageLimitReached` error if the sequence number is
+    /// exhausted.
+    pub fn seal(&mut self, aad: &[u8], plain_txt: &[u8]) -> Result<Vec<u8>, Error> {
+        let next_seq = self.next_seq()?;
+        let ctxt = self.aead.seal(
+            &self.key,
+            &compute_nonce(&self.base_nonce, self.sequence_number),
+            aad,
+            plain_txt,
+        )?;
+        self.sequence_number = next_seq;
+        Ok(ctxt)
+    }
+
+    /// Open `cipher_txt` with `aad` and the nonce for the current sequence
+    /// number, and increment the sequence number.
+    ///
+    /// Returns a `MessageLimitReached` error if the sequence number is
+    /// exhausted.
+    pub fn open(&mut self, aad: &[u8], cipher_txt: &[u8]) -> Result<Vec<u8>, Error> {
+        let next_seq = self.next_seq()?;
+        let ptxt = self.aead.open(
+            &self.key,
+            &compute_nonce(&self.base_nonce, self.sequence_number),
+            aad,
+            cipher_txt,
+        )?;
+        self.sequence_number = next_seq;
+        Ok(ptxt)
+    }
+
+    /// Get the current sequence number.
+    pub fn sequence_number(&self) -> u32 {
+        self.sequence_number
+    }
+
+    /// Get the sequence number after the current one, like
+    /// `Context.IncrementSeq` in the RFC.
+    /// The last sequence number is never used for a message.
+    fn next_seq(&self) -> Result<u32, Error> {
+        self.sequence_number
+            .checked_add(1)
+            .ok_or(Error::MessageLimitReached)
+    }
+
+    #[cfg(test)]
+    pub(crate) fn set_sequence_number(&mut self, sequence_number: u32) {
+        self.sequence_number = sequence_number;
+    }
+}
+
+impl Drop for SymmetricContext {
+    fn drop(&mut self) {
+        self.key.zeroize();
+        self.base_nonce.zeroize();
+    }
 }
@@ -1,16 +1,118 @@
+#[cfg(not(feature = "backend-ring"))]
 use evercrypt::prelude::*;
 
-use crate::aead::{AeadTrait, Error};
+use crate::aead::{sealed, AeadTrait, Error, Mode};
+
+/// The AEAD backend, evercrypt by default.
+#[cfg(not(feature = "backend-ring"))]
+mod backend {
+    use super::*;
+
+    pub(super) fn seal_detached(
+        algorithm: AeadMode,
+        key: &[u8],
+        nonce: &[u8; 12],
+        aad: &[u8],
+        plain_txt: &[u8],
+    ) -> Result<(Vec<u8>, Vec<u8>), Error> {
+        let cipher = match Aead::new(algorithm, key) {
+            Ok(c) => c,
+            Err(_) => return Err(Error::InvalidConfig),
+        };
+
+        let (ctxt, tag) = cipher.encrypt(plain_txt, nonce, aad).unwrap();
+        Ok((ctxt, tag.to_vec()))
+    }
+
+    pub(super) fn open_detached(
+        algorithm: AeadMode,
+        key: &[u8],
+        nonce: &[u8; 12],
+        aad: &[u8],
+        cipher_txt: &[u8],
+        tag: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        let cipher = match Aead::new(algorithm, key) {
+            Ok(c) => c,
+            Err(_) => return Err(Error::InvalidConfig),
+        };
+
+        // The backends (HACL* and rust-crypto) check the tag in
+        // constant time and only decrypt after it verified, so the
+        // error doesn't depend on the plain text.
+        match cipher.decrypt(cipher_txt, tag, nonce, aad) {
+            Ok(m) => Ok(m),
+            Err(_) => Err(Error::OpenError),
+        }
+    }
+}
+
+/// The AEAD backend with the `backend-ring` feature.
+#[cfg(feature = "backend-ring")]
+mod backend {
+    use super::*;
+    use ring::aead::{Aad, Algorithm, LessSafeKey, Nonce, UnboundKey};
+
+    pub(super) fn seal_detached(
+        algorithm: &'static Algorithm,
+        key: &[u8],
+        nonce: &[u8; 12],
+        aad: &[u8],
+        plain_txt: &[u8],
+    ) -> Result<(Vec<u8>, Vec<u8>), Error> {
+        let key = UnboundKey::new(algorithm, key).map_err(|_| Error::InvalidConfig)?;
+        let mut ctxt = plain_txt.to_vec();
+        let tag = LessSafeKey::new(key)
+            .seal_in_place_separate_tag(
+                Nonce::assume_unique_for_key(*nonce),
+                Aad::from(aad),
+                &mut ctxt,
+            )
+            .map_err(|_| Error::InvalidConfig)?;
+        Ok((ctxt, tag.as_ref().to_vec()))
+    }
+
+    pub(super) fn open_detached(
+        algorithm: &'static Algorithm,
+        key: &[u8],
+        nonce: &[u8; 12],
+        aad: &[u8],
+        cipher_txt: &[u8],
+        tag: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        let key = UnboundKey::new(algorithm, key).map_err(|_| Error::InvalidConfig)?;
+        let mut in_out = crate::util::concat(&[cipher_txt, tag]);
+
+        // ring checks the tag in constant time and only decrypts after it
+        // verified. The buffer is dropped on failure.
+        let ptxt_len = LessSafeKey::new(key)
+            .open_in_place(
+                Nonce::assume_unique_for_key(*nonce),
+                Aad::from(aad),
+                &mut in_out,
+            )
+            .map_err(|_| Error::OpenError)?
+            .len();
+        in_out.truncate(ptxt_len);
+        Ok(in_out)
+    }
+}
 
 macro_rules! implement_aead {
-    ($name:ident, $algorithm:expr, $key_length:literal) => {
+    ($name:ident, $mode:expr, $algorithm:expr, $ring_algorithm:expr, $key_length:literal) => {
+        /// An AEAD for use with `TypedContext`.
         #[derive(Debug)]
-        pub(crate) struct $name {}
+        pub struct $name {}
+
+        impl sealed::Sealed for $name {}
 
         impl AeadTrait for $name {
             fn new() -> Self {
                 Self {}
             }
+            fn get_mode(&self) -> Mode {
+                $mode
+            }
             fn seal(
                 &self,
                 key: &[u8],
@@ -18,50 +120,66 @@ macro_rules! implement_aead {
                 aad: &[u8],
                 plain_txt: &[u8],
             ) -> Result<Vec<u8>, Error> {
+                let (mut ctxt, tag) = self.seal_detached(key, nonce, aad, plain_txt)?;
+                ctxt.extend(tag);
+                Ok(ctxt)
+            }
+            fn open(
+                &self,
+                key: &[u8],
+                nonce: &[u8],
+                aad: &[u8],
+                cipher_txt: &[u8],
+            ) -> Result<Vec<u8>, Error> {
+                if cipher_txt.len() < 16 {
+                    return Err(Error::OpenError);
+                }
+                let (ctxt, tag) = cipher_txt.split_at(cipher_txt.len() - 16);
+                self.open_detached(key, nonce, aad, ctxt, tag)
+            }
+            fn seal_detached(
+                &self,
+                key: &[u8],
+                nonce: &[u8],
+                aad: &[u8],
+                plain_txt: &[u8],
+            ) -> Result<(Vec<u8>, Vec<u8>), Error> {
                 if nonce.len() != 12 {
                     return Err(Error::InvalidNonce);
                 }
 
-                let cipher = match Aead::new($algorithm, &key) {
-                    Ok(c) => c,
-                    Err(_) => return Err(Error::InvalidConfig),
-                };
-
                 let mut nonce_array = [0u8; 12];
                 nonce_array.clone_from_slice(nonce);
 
-                let (mut ctxt, tag) = cipher.encrypt(&plain_txt, &nonce_array, &aad).unwrap();
-                ctxt.extend(tag.to_vec());
-                Ok(ctxt)
+                #[cfg(not(feature = "backend-ring"))]
+                let algorithm = $algorithm;
+                #[cfg(feature = "backend-ring")]
+                let algorithm = &$ring_algorithm;
+                backend::seal_detached(algorithm, key, &nonce_array, aad, plain_txt)
             }
-            fn open(
+            fn open_detached(
                 &self,
                 key: &[u8],
                 nonce: &[u8],
                 aad: &[u8],
                 cipher_txt: &[u8],
+                tag: &[u8],
             ) -> Result<Vec<u8>, Error> {
                 if nonce.len() != 12 {
                     return Err(Error::InvalidNonce);
                 }
-
-                let cipher = match Aead::new($algorithm, &key) {
-                    Ok(c) => c,
-                    Err(_) => return Err(Error::InvalidConfig),
-                };
+                if tag.len() != 16 {
+                    return Err(Error::OpenError);
+                }
 
                 let mut nonce_array = [0u8; 12];
                 nonce_array.clone_from_slice(nonce);
 
-                match cipher.decrypt(
-                    &cipher_txt[..cipher_txt.len() - 16],
-                    &cipher_txt[cipher_txt.len() - 16..],
-                    &nonce_array,
-                    &aad,
-                ) {
-                    Ok(m) => Ok(m),
-                    Err(_) => Err(Error::OpenError),
-                }
+                #[cfg(not(feature = "backend-ring"))]
+                let algorithm = $algorithm;
+                #[cfg(feature = "backend-ring")]
+                let algorithm = &$ring_algorithm;
+                backend::open_detached(algorithm, key, &nonce_array, aad, cipher_txt, tag)
             }
             fn get_key_length(&self) -> usize {
                 $key_length
@@ -69,10 +187,31 @@ macro_rules! implement_aead {
             fn get_nonce_length(&self) -> usize {
                 12
             }
+            fn get_tag_length(&self) -> usize {
+                16
+            }
         }
     };
 }
 
-implement_aead!(AesGcm128, AeadMode::Aes128Gcm, 16);
-implement_aead!(AesGcm256, AeadMode::Aes256Gcm, 32);
-implement_aead!(ChaCha20Poly1305, AeadMode::Chacha20Poly1305, 32);
+implement_aead!(
+    AesGcm128,
+    Mode::AesGcm128,
+    AeadMode::Aes128Gcm,
+    ring::aead::AES_128_GCM,
+    16
+);
+implement_aead!(
+    AesGcm256,
+    Mode::AesGcm256,
+    AeadMode::Aes256Gcm,
+    ring::aead::AES_256_GCM,
+    32
+);
+implement_aead!(
+    ChaCha20Poly1305,
+    Mode::ChaCha20Poly1305,
+    AeadMode::Chacha20Poly1305,
+    ring::aead::CHACHA20_POLY1305,
+    32
+);
@@ -18,20 +18,37 @@ impl DhKem {
             sk_len: 32,
             encoded_pk_len: match dh_id {
                 ecdh::Mode::X25519 => 32,
-                ecdh::Mode::P256 => 64,
+                ecdh::Mode::P256 => 65,
             },
             kdf: kdf::Kdf::new(kdf_id),
             dh_id,
         }
     }
-    fn dh(&self, sk: &[u8], pk: &[u8]) -> Vec<u8> {
-        // Unwrapping here is fine because we have to make sure that the input
-        // keys are valid before we get here.
-        let dh = ecdh_derive(self.dh_id, pk, sk).unwrap();
+    /// Reject private keys of the wrong length, the all-zero scalar, and
+    /// P256 scalars that are not in `[1, n)`.
+    fn validate_sk(&self, sk: &[u8]) -> Result<(), Error> {
+        if sk.len() != self.sk_len || sk.iter().fold(0u8, |acc, b| acc | b) == 0 {
+            return Err(Error::InvalidInput);
+        }
+        match self.dh_id {
+            ecdh::Mode::X25519 => Ok(()),
+            ecdh::Mode::P256 => match p256_validate_sk(sk) {
+                Ok(_) => Ok(()),
+                Err(_) => Err(Error::InvalidInput),
+            },
+        }
+    }
+
+    fn dh(&self, sk: &[u8], pk: &[u8]) -> Result<Vec<u8>, Error> {
+        self.validate_sk(sk)?;
+        let dh = match ecdh_derive(self.dh_id, pk, sk) {
+            Ok(dh) => dh,
+            Err(_) => return Err(Error::InvalidInput),
+        };
 
         match self.dh_id {
-            ecdh::Mode::X25519 => dh,
-            ecdh::Mode::P256 => dh[0..32].to_vec(),
+            ecdh::Mode::X25519 => Ok(dh),
+            ecdh::Mode::P256 => Ok(dh[0..32].to_vec()),
         }
     }
 
@@ -43,11 +60,15 @@ impl DhKem {
         tmp
     }
 
-    fn dh_base(&self, sk: &[u8]) -> Vec<u8> {
-        let out = ecdh_derive_base(self.dh_id, sk).unwrap();
+    fn dh_base(&self, sk: &[u8]) -> Result<Vec<u8>, Error> {
+        self.validate_sk(sk)?;
+        let out = match ecdh_derive_base(self.dh_id, sk) {
+            Ok(out) => out,
+            Err(_) => return Err(Error::InvalidInput),
+        };
         match self.dh_id {
-            ecdh::Mode::X25519 => out,
-            ecdh::Mode::P256 => Self::nist_format_uncompressed(&out),
+            ecdh::Mode::X25519 => Ok(out),
+            ecdh::Mode::P256 => Ok(Self::nist_format_uncompressed(&out)),
         }
     }
 
@@ -82,6 +103,30 @@ impl KemTrait for DhKem {
     fn get_encoded_pk_len(&self) -> usize {
         self.encoded_pk_len
     }
+    fn get_sk_len(&self) -> usize {
+        self.sk_len
+    }
+
+    fn sk_to_pk(&self, sk: &[u8]) -> Result<PublicKey, Error> {
+        self.dh_base(sk)
+    }
+
+    fn enc_to_pk(&self, enc: &[u8]) -> Result<PublicKey, Error> {
+        if enc.len() != self.encoded_pk_len {
+            return Err(Error::InvalidInput);
+        }
+        let pk = self.deserialize(enc);
+        match self.dh_id {
+            ecdh::Mode::X25519 => Ok(pk),
+            // Uncompressed points are prefixed with 0x04.
+            ecdh::Mode::P256 => {
+                if pk[0] != 0x04 || p256_validate_pk(&pk[1..]).is_err() {
+                    return Err(Error::InvalidInput);
+                }
+                Ok(pk)
+            }
+        }
+    }
 
     fn new(_kdf_id: kdf::Mode) -> Self {
         panic!("Don't use this please");
@@ -89,7 +134,8 @@ impl KemTrait for DhKem {
 
     fn key_gen(&self) -> (Vec<u8>, Vec<u8>) {
         let sk = ecdh::key_gen(self.dh_id);
-        let pk = self.dh_base(&sk);
+        // Unwrapping here is fine because the generated key is always valid.
+        let pk = self.dh_base(&sk).unwrap();
         (sk, pk)
     }
 
@@ -125,51 +171,69 @@ impl KemTrait for DhKem {
                 }
             }
         };
-        (self.dh_base(&sk).to_vec(), sk)
-    }
-
-    fn encaps(&self, pk_r: &[u8], suite_id: &[u8]) -> (Vec<u8>, Vec<u8>) {
-        let (pk_e, sk_e) = self.derive_key_pair(&get_random_vec(self.get_secret_len()), suite_id);
-        let dh_pk = self.dh(&sk_e, pk_r);
+        // Unwrapping here is fine because the derived key is always valid.
+        (self.dh_base(&sk).unwrap(), sk)
+    }
+
+    fn encaps(
+        &self,
+        pk_r: &[u8],
+        ikm_e: &[u8],
+        suite_id: &[u8],
+    ) -> Result<(Vec<u8>, Vec<u8>), Error> {
+        let (pk_e, sk_e) = self.derive_key_pair(suite_id, ikm_e);
+        let dh_pk = self.dh(&sk_e, pk_r)?;
         let enc = self.serialize(&pk_e);
 
         let pk_rm = self.serialize(pk_r);
         let kem_context = concat(&[&enc, &pk_rm]);
 
         let zz = self.extract_and_expand(dh_pk.to_vec(), &kem_context, suite_id);
-        (zz, enc)
+        Ok((zz, enc))
     }
 
-    fn decaps(&self, enc: &[u8], sk_r: &[u8], suite_id: &[u8]) -> Vec<u8> {
+    fn decaps(&self, enc: &[u8], sk_r: &[u8], suite_id: &[u8]) -> Result<Vec<u8>, Error> {
         let pk_e = self.deserialize(enc);
-        let dh_pk = self.dh(sk_r, &pk_e);
+        let dh_pk = self.dh(sk_r, &pk_e)?;
 
-        let pk_rm = self.serialize(&self.dh_base(sk_r));
+        let pk_rm = self.serialize(&self.dh_base(sk_r)?);
         let kem_context = concat(&[&enc, &pk_rm]);
 
-        self.extract_and_expand(dh_pk.to_vec(), &kem_context, suite_id)
+        Ok(self.extract_and_expand(dh_pk.to_vec(), &kem_context, suite_id))
     }
-    fn auth_encaps(&self, pk_r: &[u8], sk_s: &[u8], suite_id: &[u8]) -> (Vec<u8>, Vec<u8>) {
-        let (pk_e, sk_e) = self.derive_key_pair(&get_random_vec(self.get_secret_len()), suite_id);
-        let dh_pk = concat(&[&self.dh(&sk_e, pk_r), &self.dh(&sk_s, pk_r)]);
+    fn auth_encaps(
+        &self,
+        pk_r: &[u8],
+        sk_s: &[u8],
+        ikm_e: &[u8],
+        suite_id: &[u8],
+    ) -> Result<(Vec<u8>, Vec<u8>), Error> {
+        let (pk_e, sk_e) = self.derive_key_pair(suite_id, ikm_e);
+        let dh_pk = concat(&[&self.dh(&sk_e, pk_r)?, &self.dh(&sk_s, pk_r)?]);
 
         let enc = self.serialize(&pk_e);
         let pk_rm = self.serialize(&pk_r);
-        let pk_sm = self.serialize(&self.dh_base(&sk_s));
+        let pk_sm = self.serialize(&self.dh_base(&sk_s)?);
 
         let kem_context = concat(&[&enc, &pk_rm, &pk_sm]);
 
         let zz = self.extract_and_expand(dh_pk.to_vec(), &kem_context, suite_id);
-        (zz, enc)
-    }
-    fn auth_decaps(&self, enc: &[u8], sk_r: &[u8], pk_s: &[u8], suite_id: &[u8]) -> Vec<u8> {
+        Ok((zz, enc))
+    }
+    fn auth_decaps(
+        &self,
+        enc: &[u8],
+        sk_r: &[u8],
+        pk_s: &[u8],
+        suite_id: &[u8],
+    ) -> Result<Vec<u8>, Error> {
         let pk_e = self.deserialize(enc);
-        let dh_pk = concat(&[&self.dh(sk_r, &pk_e), &self.dh(sk_r, &pk_s)]);
+        let dh_pk = concat(&[&self.dh(sk_r, &pk_e)?, &self.dh(sk_r, &pk_s)?]);
 
-        let pk_rm = self.serialize(&self.dh_base(sk_r));
+        let pk_rm = self.serialize(&self.dh_base(sk_r)?);
         let pk_sm = self.serialize(&pk_s);
         let kem_context = concat(&[&enc, &pk_rm, &pk_sm]);
 
-        self.extract_and_expand(dh_pk.to_vec(), &kem_context, suite_id)
+        Ok(self.extract_and_expand(dh_pk.to_vec(), &kem_context, suite_id))
     }
 }
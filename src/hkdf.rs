@@ -20,6 +20,9 @@ macro_rules! implement_hkdfs {
             fn expand(&self, prk: &[u8], info: &[u8], output_size: usize) -> Vec<u8> {
                 hkdf_expand($hmac_mode, &prk, &info, output_size)
             }
+            fn hmac(&self, key: &[u8], data: &[u8]) -> Vec<u8> {
+                hmac($hmac_mode, key, data, None)
+            }
         }
     };
 }
@@ -5,6 +5,7 @@ use crate::hkdf;
 use crate::util::concat;
 
 use std::fmt::Debug;
+use zeroize::Zeroize;
 
 /// KDF Modes
 #[derive(PartialEq, Copy, Clone, Debug)]
@@ -39,6 +40,10 @@ impl std::convert::TryFrom<u16> for Mode {
     }
 }
 
+/// The KDF modes implemented by this build.
+pub(crate) const SUPPORTED_MODES: [Mode; 3] =
+    [Mode::HkdfSha256, Mode::HkdfSha384, Mode::HkdfSha512];
+
 /// KDF Errors
 #[derive(Debug)]
 pub enum Error {
@@ -46,12 +51,13 @@ pub enum Error {
     UnknownMode,
 }
 
-pub(crate) trait KdfTrait: Debug + Sync {
+pub(crate) trait KdfTrait: Debug + Send + Sync {
     fn new() -> Self
     where
         Self: Sized;
     fn extract(&self, salt: &[u8], ikm: &[u8]) -> Vec<u8>;
     fn expand(&self, prk: &[u8], info: &[u8], output_size: usize) -> Vec<u8>;
+    fn hmac(&self, key: &[u8], data: &[u8]) -> Vec<u8>;
     fn digest_length(&self) -> usize;
 }
 
@@ -113,12 +119,91 @@ impl Kdf {
         info: &[u8],
         len: usize,
     ) -> Vec<u8> {
-        assert!(len < 256);
-        let len_bytes = (len as u16).to_be_bytes();
-        let labeled_info = concat(&[&len_bytes, b"HPKE-06", suite_id, &label.as_bytes(), info]);
+        let mut labeled_info = Vec::new();
+        write_labeled_info(&mut labeled_info, suite_id, label, info, len);
         self.kdf.expand(prk, &labeled_info, len)
     }
 
+    /// Like `labeled_expand` but write the output into `out`.
+    ///
+    /// The old content of `out` is zeroized and its buffer is reused.
+    /// `labeled_info` is scratch space for the labeled info, so that it can be
+    /// reused across calls. The HKDF backend still returns the output in a
+    /// temporary buffer, which is zeroized after it's copied into `out`.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn labeled_expand_into(
+        &self,
+        prk: &[u8],
+        suite_id: &[u8],
+        label: &'static str,
+        info: &[u8],
+        len: usize,
+        labeled_info: &mut Vec<u8>,
+        out: &mut Vec<u8>,
+    ) {
+        write_labeled_info(labeled_info, suite_id, label, info, len);
+        let mut okm = self.kdf.expand(prk, labeled_info, len);
+        out.zeroize();
+        out.extend_from_slice(&okm);
+        okm.zeroize();
+    }
+
+    /// Run `labeled_expand` for each `(label, len)` in `outputs` with the same
+    /// `prk` and `info`.
+    ///
+    /// The outputs are identical to separate `labeled_expand` calls. The
+    /// labeled info buffer is reused across all calls.
+    pub(crate) fn labeled_expand_multi(
+        &self,
+        prk: &[u8],
+        suite_id: &[u8],
+        info: &[u8],
+        outputs: &[(&'static str, usize)],
+    ) -> Vec<Vec<u8>> {
+        let mut labeled_info = Vec::new();
+        outputs
+            .iter()
+            .map(|&(label, len)| {
+                write_labeled_info(&mut labeled_info, suite_id, label, info, len);
+                self.kdf.expand(prk, &labeled_info, len)
+            })
+            .collect()
+    }
+
+    /// Get the labeled info that `labeled_expand` passes to HKDF-Expand.
+    pub(crate) fn labeled_info(
+        &self,
+        suite_id: &[u8],
+        label: &'static str,
+        info: &[u8],
+        len: usize,
+    ) -> Vec<u8> {
+        let mut labeled_info = Vec::new();
+        write_labeled_info(&mut labeled_info, suite_id, label, info, len);
+        labeled_info
+    }
+
+    /// Compute the HKDF-Expand output block `T(counter)` from the previous
+    /// block `previous` (`T(counter - 1)`).
+    ///
+    /// ```text
+    /// T(counter) = HMAC-Hash(prk, T(counter - 1) | info | counter)
+    /// ```
+    pub(crate) fn expand_block(
+        &self,
+        prk: &[u8],
+        previous: &[u8],
+        info: &[u8],
+        counter: u8,
+    ) -> Vec<u8> {
+        self.kdf.hmac(prk, &concat(&[previous, info, &[counter]]))
+    }
+
+    /// Compute `HMAC-Hash(key, data)` with the hash of this KDF.
+    pub(crate) fn hmac(&self, key: &[u8], data: &[u8]) -> Vec<u8> {
+        self.kdf.hmac(key, data)
+    }
+
     #[cfg(test)]
     pub(crate) fn extract(&self, salt: &[u8], ikm: &[u8]) -> Vec<u8> {
         self.kdf.extract(salt, ikm)
@@ -130,6 +215,18 @@ impl Kdf {
     }
 }
 
+/// Write `concat(I2OSP(len, 2), "HPKE-06", suite_id, label, info)` into `out`.
+#[inline]
+fn write_labeled_info(out: &mut Vec<u8>, suite_id: &[u8], label: &str, info: &[u8], len: usize) {
+    assert!(len <= u16::MAX as usize);
+    out.clear();
+    out.extend_from_slice(&(len as u16).to_be_bytes());
+    out.extend_from_slice(b"HPKE-06");
+    out.extend_from_slice(suite_id);
+    out.extend_from_slice(label.as_bytes());
+    out.extend_from_slice(info);
+}
+
 fn get_kdf_object(mode: Mode) -> Box<dyn KdfTrait> {
     match mode {
         Mode::HkdfSha256 => Box::new(hkdf::HkdfSha256::new()),
@@ -4,6 +4,7 @@ pub(crate) use serde::{Deserialize, Serialize};
 use crate::dh_kem;
 use crate::kdf;
 use crate::util;
+use crate::HPKEKeyPair;
 
 /// KEM Modes
 #[derive(PartialEq, Copy, Clone, Debug)]
@@ -46,11 +47,20 @@ impl std::convert::TryFrom<u16> for Mode {
     }
 }
 
+/// The KEM modes implemented by this build.
+pub(crate) const SUPPORTED_MODES: [Mode; 2] = [Mode::DhKemP256, Mode::DhKem25519];
+
 /// KEM Errors
 #[derive(Debug)]
 pub enum Error {
     /// The KEM mode is unknown.
     UnknownMode,
+
+    /// An invalid key or encapsulation was passed to the KEM.
+    InvalidInput,
+
+    /// The operation is not supported by the KEM.
+    Unsupported,
 }
 
 // Map KEM to KDF according to spec.
@@ -64,10 +74,34 @@ fn get_kdf(mode: Mode) -> kdf::Mode {
     }
 }
 
+/// Combine the shared secret `components` of several KEMs into a single
+/// shared secret.
+///
+/// A single component is returned unchanged, so that single KEM suites are
+/// interoperable with the RFC.
+/// Multiple components are encoded canonically by prefixing each with its
+/// length as two byte big-endian integer.
+///
+/// ```text
+/// combined = concat(I2OSP(len(c_1), 2), c_1, ..., I2OSP(len(c_n), 2), c_n)
+/// ```
+pub(crate) fn combine_shared_secrets(components: &[&[u8]]) -> Vec<u8> {
+    if let [component] = components {
+        return component.to_vec();
+    }
+    let mut combined = Vec::new();
+    for component in components {
+        assert!(component.len() <= u16::MAX as usize);
+        combined.extend_from_slice(&(component.len() as u16).to_be_bytes());
+        combined.extend_from_slice(component);
+    }
+    combined
+}
+
 pub(crate) type PrivateKey = Vec<u8>;
 pub(crate) type PublicKey = Vec<u8>;
 
-pub(crate) trait KemTrait: std::fmt::Debug + Sync {
+pub(crate) trait KemTrait: std::fmt::Debug + Send + Sync {
     fn new(kdf_id: kdf::Mode) -> Self
     where
         Self: Sized;
@@ -75,15 +109,46 @@ pub(crate) trait KemTrait: std::fmt::Debug + Sync {
     fn key_gen(&self) -> (Vec<u8>, Vec<u8>);
     fn derive_key_pair(&self, suite_id: &[u8], ikm: &[u8]) -> (PublicKey, PrivateKey);
 
-    fn encaps(&self, pk_r: &[u8], suite_id: &[u8]) -> (Vec<u8>, Vec<u8>);
-    fn decaps(&self, enc: &[u8], sk_r: &[u8], suite_id: &[u8]) -> Vec<u8>;
-    fn auth_encaps(&self, pk_r: &[u8], sk_s: &[u8], suite_id: &[u8]) -> (Vec<u8>, Vec<u8>);
-    fn auth_decaps(&self, enc: &[u8], sk_r: &[u8], pk_s: &[u8], suite_id: &[u8]) -> Vec<u8>;
+    /// Encapsulate to `pk_r` with an ephemeral key pair derived from `ikm_e`.
+    fn encaps(
+        &self,
+        pk_r: &[u8],
+        ikm_e: &[u8],
+        suite_id: &[u8],
+    ) -> Result<(Vec<u8>, Vec<u8>), Error>;
+    fn decaps(&self, enc: &[u8], sk_r: &[u8], suite_id: &[u8]) -> Result<Vec<u8>, Error>;
+    fn auth_encaps(
+        &self,
+        pk_r: &[u8],
+        sk_s: &[u8],
+        ikm_e: &[u8],
+        suite_id: &[u8],
+    ) -> Result<(Vec<u8>, Vec<u8>), Error>;
+    fn auth_decaps(
+        &self,
+        enc: &[u8],
+        sk_r: &[u8],
+        pk_s: &[u8],
+        suite_id: &[u8],
+    ) -> Result<Vec<u8>, Error>;
 
     fn get_secret_len(&self) -> usize;
     fn get_encoded_pk_len(&self) -> usize;
+    fn get_sk_len(&self) -> usize;
+
+    /// Compute the public key for the private key `sk`.
+    fn sk_to_pk(&self, sk: &[u8]) -> Result<PublicKey, Error>;
+
+    /// Get the validated ephemeral public key from the encapsulated secret
+    /// `enc`.
+    /// This is only possible for DH based KEMs.
+    fn enc_to_pk(&self, _enc: &[u8]) -> Result<PublicKey, Error> {
+        Err(Error::Unsupported)
+    }
 }
 
+/// A KEM instance, e.g. to generate key pairs without an `Hpke`
+/// configuration.
 #[derive(Debug)]
 pub struct Kem {
     mode: Mode,
@@ -133,26 +198,80 @@ impl Kem {
         }
     }
 
+    /// Create a KEM for `mode`.
+    ///
+    /// Returns an `Unsupported` error if `mode` is not implemented.
+    pub fn try_new(mode: Mode) -> Result<Self, Error> {
+        if !SUPPORTED_MODES.contains(&mode) {
+            return Err(Error::Unsupported);
+        }
+        Ok(Self::new(mode))
+    }
+
     #[inline]
     fn get_ciphersuite(&self) -> Vec<u8> {
         util::concat(&[b"KEM", &(self.mode as u16).to_be_bytes()])
     }
 
-    pub(crate) fn encaps(&self, pk_r: &[u8]) -> (Vec<u8>, Vec<u8>) {
-        self.kem.encaps(pk_r, &self.get_ciphersuite())
+    pub(crate) fn encaps(&self, pk_r: &[u8], ikm_e: &[u8]) -> Result<(Vec<u8>, Vec<u8>), Error> {
+        self.kem.encaps(pk_r, ikm_e, &self.get_ciphersuite())
     }
-    pub(crate) fn decaps(&self, enc: &[u8], sk_r: &[u8]) -> Vec<u8> {
+    pub(crate) fn decaps(&self, enc: &[u8], sk_r: &[u8]) -> Result<Vec<u8>, Error> {
         self.kem.decaps(enc, sk_r, &self.get_ciphersuite())
     }
-    pub(crate) fn auth_encaps(&self, pk_r: &[u8], sk_s: &[u8]) -> (Vec<u8>, Vec<u8>) {
-        self.kem.auth_encaps(pk_r, sk_s, &self.get_ciphersuite())
+    pub(crate) fn auth_encaps(
+        &self,
+        pk_r: &[u8],
+        sk_s: &[u8],
+        ikm_e: &[u8],
+    ) -> Result<(Vec<u8>, Vec<u8>), Error> {
+        self.kem
+            .auth_encaps(pk_r, sk_s, ikm_e, &self.get_ciphersuite())
     }
-    pub(crate) fn auth_decaps(&self, enc: &[u8], sk_r: &[u8], pk_s: &[u8]) -> Vec<u8> {
+    pub(crate) fn auth_decaps(
+        &self,
+        enc: &[u8],
+        sk_r: &[u8],
+        pk_s: &[u8],
+    ) -> Result<Vec<u8>, Error> {
         self.kem
             .auth_decaps(enc, sk_r, pk_s, &self.get_ciphersuite())
     }
-    pub(crate) fn key_gen(&self) -> (Vec<u8>, Vec<u8>) {
-        self.kem.key_gen()
+    /// Generate a fresh key pair.
+    pub fn generate_key_pair(&self) -> HPKEKeyPair {
+        let (sk, pk) = self.kem.key_gen();
+        HPKEKeyPair::new(sk, pk)
+    }
+
+    /// Compute the public key for the private key `sk`.
+    pub(crate) fn sk_to_pk(&self, sk: &[u8]) -> Result<PublicKey, Error> {
+        self.kem.sk_to_pk(sk)
+    }
+
+    /// Get the validated ephemeral public key from the encapsulated secret
+    /// `enc`.
+    pub(crate) fn enc_to_pk(&self, enc: &[u8]) -> Result<PublicKey, Error> {
+        self.kem.enc_to_pk(enc)
+    }
+
+    /// Get the length of an encapsulated secret (`Nenc`).
+    pub(crate) fn enc_len(&self) -> usize {
+        self.kem.get_encoded_pk_len()
+    }
+
+    /// Get the length of a private key (`Nsk`).
+    pub(crate) fn sk_len(&self) -> usize {
+        self.kem.get_sk_len()
+    }
+
+    /// Get the length of an encoded public key (`Npk`).
+    pub(crate) fn pk_len(&self) -> usize {
+        self.kem.get_encoded_pk_len()
+    }
+
+    /// Get the length of the shared secret produced by this KEM (`Nsecret`).
+    pub(crate) fn shared_secret_len(&self) -> usize {
+        self.kem.get_secret_len()
     }
 
     /// Derive key pair from the input key material `ikm`.
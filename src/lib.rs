@@ -15,19 +15,35 @@
 #[cfg(feature = "serialization")]
 pub(crate) use serde::{Deserialize, Serialize};
 
+pub use rand_core::CryptoRngCore;
+use zeroize::Zeroize;
+pub use zeroize::Zeroizing;
+
+#[cfg(feature = "replay-guard")]
+pub use replay::{MemoryReplayStore, ReplayStore};
+
 pub(crate) mod aead;
 mod aead_impl;
 mod dh_kem;
 mod hkdf;
 pub(crate) mod kdf;
 pub(crate) mod kem;
+#[cfg(feature = "pem")]
+mod pem;
+#[cfg(feature = "pkcs8")]
+mod pkcs8;
 pub mod prelude;
+#[cfg(feature = "replay-guard")]
+mod replay;
+mod self_test;
 
 mod util;
 
 #[cfg(test)]
 mod test_aead;
 #[cfg(test)]
+mod test_hpke;
+#[cfg(test)]
 mod test_kdf;
 
 /// HPKE Error types.
@@ -48,14 +64,42 @@ pub enum HPKEError {
     /// Inconsistent PSK input.
     InconsistentPsk,
 
-    /// PSK input is required but missing.
-    MissingPsk,
+    /// PSK input doesn't match the mode.
+    /// Either the PSK is required but missing or it is provided but not needed.
+    PskMismatch {
+        /// The mode requires a PSK.
+        expected_psk: bool,
 
-    /// PSK input is provided but not needed.
-    UnnecessaryPsk,
+        /// A PSK was provided.
+        got_psk: bool,
+    },
 
     /// PSK input is too short (needs to be at least 32 bytes).
     InsecurePsk,
+
+    /// Error reading from or writing to an IO stream.
+    Io,
+
+    /// The nonce has an invalid length.
+    /// Nonces are derived internally, so this indicates a bug.
+    InvalidNonce,
+
+    /// The suite isn't supported by this implementation, see
+    /// `Hpke::try_new`.
+    UnsupportedSuite,
+
+    /// The sender's public key was rejected by the certificate verifier.
+    CertificateRejected,
+
+    /// The known-answer self-test failed.
+    SelfTestFailed,
+
+    /// The encapsulated secret was seen before (see `Hpke::open_checked`).
+    Replay,
+
+    /// The sequence number of the context is exhausted and no more messages
+    /// can be sealed or opened.
+    MessageLimitReached,
 }
 
 /// An HPKE public key is a byte vector.
@@ -72,6 +116,30 @@ pub struct HPKEPrivateKey {
     value: Vec<u8>,
 }
 
+/// All intermediate values of the key schedule.
+///
+/// Note that there's no `psk_hash` in this version of the key schedule. The
+/// PSK goes directly into `secret`.
+#[cfg(feature = "debug-trace")]
+#[doc(hidden)]
+#[derive(Debug, Clone)]
+pub struct KeyScheduleTrace {
+    /// `LabeledExtract("", "psk_id_hash", psk_id)`
+    pub psk_id_hash: Vec<u8>,
+    /// `LabeledExtract("", "info_hash", info)`
+    pub info_hash: Vec<u8>,
+    /// `concat(mode, psk_id_hash, info_hash)`
+    pub key_schedule_context: Vec<u8>,
+    /// `LabeledExtract(shared_secret, "secret", psk)`
+    pub secret: Vec<u8>,
+    /// The AEAD key.
+    pub key: Vec<u8>,
+    /// The AEAD base nonce.
+    pub base_nonce: Vec<u8>,
+    /// The exporter secret.
+    pub exporter_secret: Vec<u8>,
+}
+
 /// An HPKE key pair has an HPKE private and public key.
 #[derive(Debug, Default)]
 #[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
@@ -80,6 +148,14 @@ pub struct HPKEKeyPair {
     public_key: HPKEPublicKey,
 }
 
+/// A pre-shared key `secret` with its identifier `id` for the PSK modes.
+///
+/// Use `Psk::new` to create a PSK.
+pub struct Psk {
+    id: Vec<u8>,
+    secret: Vec<u8>,
+}
+
 /// HPKE supports four modes.
 #[derive(PartialEq, Copy, Clone, Debug)]
 #[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
@@ -129,6 +205,20 @@ type Ciphertext = Vec<u8>;
 /// A byte vector.
 type Plaintext = Vec<u8>;
 
+/// Type alias for AEAD tags.
+/// A byte vector.
+type Tag = Vec<u8>;
+
+/// The replay risk of data opened with `Hpke::open_early_data`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[must_use]
+pub enum ReplayRisk {
+    /// The data may have been replayed by an attacker.
+    /// HPKE can't detect this; the caller has to apply its own anti-replay
+    /// mechanism before acting on the data.
+    Unchecked,
+}
+
 /// The HPKE context.
 /// Note that the RFC currently doesn't define this.
 /// Also see https://github.com/cfrg/draft-irtf-cfrg-hpke/issues/161.
@@ -137,9 +227,32 @@ pub struct Context<'a> {
     nonce: Vec<u8>,
     exporter_secret: Vec<u8>,
     sequence_number: u32,
+    external_counter: bool,
+    refresh_epoch: u64,
+    aad_prefix: Vec<u8>,
     hpke: &'a Hpke,
 }
 
+/// Non-secret metadata of a `Context`, e.g. for logging.
+///
+/// Use `Context::metadata` to get it. It never contains key material.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub struct ContextMetadata {
+    /// The HPKE mode.
+    pub mode: Mode,
+    /// The KEM of the suite.
+    pub kem_id: kem::Mode,
+    /// The KDF of the suite.
+    pub kdf_id: kdf::Mode,
+    /// The AEAD of the suite.
+    pub aead_id: aead::Mode,
+    /// The current sequence number.
+    pub sequence_number: u32,
+    /// The session fingerprint, which is the same for both peers.
+    pub fingerprint: [u8; 8],
+}
+
 #[cfg(feature = "hazmat")]
 impl<'a> std::fmt::Debug for Context<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -175,11 +288,14 @@ impl<'a> Context<'a> {
     ///   return ct
     /// ```
     pub fn seal(&mut self, aad: &[u8], plain_txt: &[u8]) -> Result<Ciphertext, HPKEError> {
-        let ctxt = self
-            .hpke
-            .aead
-            .seal(&self.key, &self.compute_nonce(), aad, plain_txt)?;
-        self.increment_seq();
+        self.check_seal_limits(aad.len(), plain_txt.len())?;
+        let ctxt = self.hpke.aead.seal(
+            &self.key,
+            &self.compute_nonce()?,
+            &self.get_aad(aad),
+            plain_txt,
+        )?;
+        self.increment_seq()?;
         Ok(ctxt)
     }
 
@@ -197,14 +313,522 @@ impl<'a> Context<'a> {
     ///   return pt
     /// ```
     pub fn open(&mut self, aad: &[u8], cipher_txt: &[u8]) -> Result<Plaintext, HPKEError> {
+        self.check_open_limits(aad.len(), cipher_txt.len())?;
+        let ptxt = self.hpke.aead.open(
+            &self.key,
+            &self.compute_nonce()?,
+            &self.get_aad(aad),
+            cipher_txt,
+        )?;
+        self.increment_seq()?;
+        Ok(ptxt)
+    }
+
+    /// Open `cipher_txt` like `open` without incrementing the sequence number.
+    ///
+    /// This allows checking that a ciphertext is authentic before accepting it.
+    /// Note that the same ciphertext opens again with the next `peek_open` or
+    /// `open` call. Call `open` to accept the message and move on to the next
+    /// one; otherwise the following messages fail to open.
+    pub fn peek_open(&self, aad: &[u8], cipher_txt: &[u8]) -> Result<Plaintext, HPKEError> {
+        self.check_open_limits(aad.len(), cipher_txt.len())?;
+        let ptxt = self.hpke.aead.open(
+            &self.key,
+            &self.compute_nonce()?,
+            &self.get_aad(aad),
+            cipher_txt,
+        )?;
+        Ok(ptxt)
+    }
+
+    /// Seal `plain_txt` like `seal` and zeroize it afterwards.
+    ///
+    /// The plain text is zeroized even if sealing fails.
+    pub fn seal_consuming(
+        &mut self,
+        aad: &[u8],
+        mut plain_txt: Vec<u8>,
+    ) -> Result<Ciphertext, HPKEError> {
+        self.seal_and_zeroize(aad, &mut plain_txt)
+    }
+
+    /// Seal `plain_txt` like `seal` and zeroize it afterwards.
+    fn seal_and_zeroize(
+        &mut self,
+        aad: &[u8],
+        plain_txt: &mut Vec<u8>,
+    ) -> Result<Ciphertext, HPKEError> {
+        let ctxt = self.seal(aad, plain_txt);
+        plain_txt.zeroize();
+        ctxt
+    }
+
+    /// Seal `plain_txt` like `seal` and return the ciphertext body and the
+    /// `Nt` byte tag separately.
+    ///
+    /// `concat(ct, tag)` is the output of `seal`.
+    pub fn seal_detached(
+        &mut self,
+        aad: &[u8],
+        plain_txt: &[u8],
+    ) -> Result<(Ciphertext, Tag), HPKEError> {
+        self.check_seal_limits(aad.len(), plain_txt.len())?;
+        let ctxt_tag = self.hpke.aead.seal_detached(
+            &self.key,
+            &self.compute_nonce()?,
+            &self.get_aad(aad),
+            plain_txt,
+        )?;
+        self.increment_seq()?;
+        Ok(ctxt_tag)
+    }
+
+    /// Open a ciphertext body `cipher_txt` with the separate `tag` like
+    /// `open`.
+    pub fn open_detached(
+        &mut self,
+        aad: &[u8],
+        cipher_txt: &[u8],
+        tag: &[u8],
+    ) -> Result<Plaintext, HPKEError> {
+        self.check_open_limits(aad.len(), cipher_txt.len() + tag.len())?;
+        let ptxt = self.hpke.aead.open_detached(
+            &self.key,
+            &self.compute_nonce()?,
+            &self.get_aad(aad),
+            cipher_txt,
+            tag,
+        )?;
+        self.increment_seq()?;
+        Ok(ptxt)
+    }
+
+    /// Seal `plain_txt` like `seal` with `Bytes` input and output.
+    ///
+    /// The ciphertext buffer is handed to `Bytes` without copying it.
+    #[cfg(feature = "bytes")]
+    pub fn seal_bytes(
+        &mut self,
+        aad: &[u8],
+        plain_txt: bytes::Bytes,
+    ) -> Result<bytes::Bytes, HPKEError> {
+        Ok(self.seal(aad, &plain_txt)?.into())
+    }
+
+    /// Open `cipher_txt` like `open` with `Bytes` input and output.
+    ///
+    /// The plain text buffer is handed to `Bytes` without copying it.
+    #[cfg(feature = "bytes")]
+    pub fn open_bytes(
+        &mut self,
+        aad: &[u8],
+        cipher_txt: bytes::Bytes,
+    ) -> Result<bytes::Bytes, HPKEError> {
+        Ok(self.open(aad, &cipher_txt)?.into())
+    }
+
+    /// Seal `plain_txt` like `seal` and additionally return the nonce that was
+    /// used.
+    ///
+    /// This allows auditing that no nonce is ever reused within a context.
+    #[cfg(feature = "audit")]
+    pub fn seal_audited(
+        &mut self,
+        aad: &[u8],
+        plain_txt: &[u8],
+    ) -> Result<(Ciphertext, Vec<u8>), HPKEError> {
+        let nonce = self.compute_nonce()?;
+        let ctxt = self.seal(aad, plain_txt)?;
+        Ok((ctxt, nonce))
+    }
+
+    /// Seal `plain_txt` with an explicit `nonce` instead of the nonce derived
+    /// from the sequence number.
+    ///
+    /// **This is an interop escape hatch that is not part of the RFC.**
+    /// It sidesteps the sequence number and with it the protection against
+    /// nonce reuse. The caller must ensure that a nonce is never reused.
+    /// The sequence number is not changed.
+    pub fn seal_with_nonce(
+        &self,
+        nonce: &[u8],
+        aad: &[u8],
+        plain_txt: &[u8],
+    ) -> Result<Ciphertext, HPKEError> {
+        if nonce.len() != self.hpke.nn {
+            return Err(HPKEError::InvalidNonce);
+        }
+        self.check_seal_limits(aad.len(), plain_txt.len())?;
+        let ctxt = self
+            .hpke
+            .aead
+            .seal(&self.key, nonce, &self.get_aad(aad), plain_txt)?;
+        Ok(ctxt)
+    }
+
+    /// Open `cipher_txt` with an explicit `nonce` instead of the nonce derived
+    /// from the sequence number.
+    ///
+    /// **This is an interop escape hatch that is not part of the RFC.**
+    /// See `seal_with_nonce`.
+    /// The sequence number is not changed.
+    pub fn open_with_nonce(
+        &self,
+        nonce: &[u8],
+        aad: &[u8],
+        cipher_txt: &[u8],
+    ) -> Result<Plaintext, HPKEError> {
+        if nonce.len() != self.hpke.nn {
+            return Err(HPKEError::InvalidNonce);
+        }
+        self.check_open_limits(aad.len(), cipher_txt.len())?;
+        let ptxt = self
+            .hpke
+            .aead
+            .open(&self.key, nonce, &self.get_aad(aad), cipher_txt)?;
+        Ok(ptxt)
+    }
+
+    /// Get the current sequence number as `Nn` byte big-endian integer.
+    ///
+    /// This is the encoding that is XORed with the base nonce in
+    /// `ComputeNonce`, e.g. to put the sequence number on the wire.
+    pub fn seq_bytes(&self) -> Vec<u8> {
+        aead::encode_seq(self.sequence_number, self.hpke.nn)
+    }
+
+    /// Set the sequence number from its `Nn` byte big-endian encoding as
+    /// returned by `seq_bytes`.
+    ///
+    /// Returns an `InvalidInput` error if `seq` isn't `Nn` bytes long or
+    /// not smaller than the maximum sequence number of this context, i.e. if
+    /// no message could be sealed or opened with it.
+    pub fn set_seq_bytes(&mut self, seq: &[u8]) -> Result<(), HPKEError> {
+        if seq.len() != self.hpke.nn {
+            return Err(HPKEError::InvalidInput);
+        }
+        let (high, low) = seq.split_at(seq.len() - 4);
+        if high.iter().any(|&b| b != 0) {
+            return Err(HPKEError::InvalidInput);
+        }
+        let seq = u32::from_be_bytes([low[0], low[1], low[2], low[3]]);
+        if seq as u128 >= self.max_seq() {
+            return Err(HPKEError::InvalidInput);
+        }
+        self.sequence_number = seq;
+        Ok(())
+    }
+
+    /// Use an external counter instead of the sequence number of this context.
+    ///
+    /// This is for systems that keep the sequence number in an external
+    /// durable store, e.g. to survive crashes without reusing a nonce.
+    /// All methods that use the internal sequence number, e.g. `seal` and
+    /// `open`, return an `InvalidConfig` error on the returned context. Use
+    /// `seal_external_seq` and `open_external_seq` instead.
+    pub fn with_external_counter(mut self) -> Self {
+        self.external_counter = true;
+        self
+    }
+
+    /// Seal `plain_txt` with the nonce for the externally managed sequence
+    /// number `seq`.
+    ///
+    /// The ciphertext is the same that `seal` produces when the sequence
+    /// number of the context is `seq`. The sequence number of this context is
+    /// not used or changed.
+    /// The caller must ensure that `seq` is never reused, e.g. by only ever
+    /// incrementing it.
+    /// Returns an `InvalidInput` error if `seq` is larger than the maximum
+    /// sequence number of this context.
+    pub fn seal_external_seq(
+        &self,
+        seq: u128,
+        aad: &[u8],
+        plain_txt: &[u8],
+    ) -> Result<Ciphertext, HPKEError> {
+        let nonce = self.nonce_for_seq(seq)?;
+        self.check_seal_limits(aad.len(), plain_txt.len())?;
+        let ctxt = self
+            .hpke
+            .aead
+            .seal(&self.key, &nonce, &self.get_aad(aad), plain_txt)?;
+        Ok(ctxt)
+    }
+
+    /// Open `cipher_txt` with the nonce for the externally managed sequence
+    /// number `seq`.
+    ///
+    /// See `seal_external_seq`.
+    pub fn open_external_seq(
+        &self,
+        seq: u128,
+        aad: &[u8],
+        cipher_txt: &[u8],
+    ) -> Result<Plaintext, HPKEError> {
+        let nonce = self.nonce_for_seq(seq)?;
+        self.check_open_limits(aad.len(), cipher_txt.len())?;
         let ptxt = self
             .hpke
             .aead
-            .open(&self.key, &self.compute_nonce(), aad, cipher_txt)?;
-        self.increment_seq();
+            .open(&self.key, &nonce, &self.get_aad(aad), cipher_txt)?;
+        Ok(ptxt)
+    }
+
+    /// Open `cipher_txt` like `open` with the aad given as `aad_parts`.
+    ///
+    /// The parts are concatenated, i.e. this is equivalent to `open` with
+    /// `concat(aad_parts)` as aad.
+    pub fn open_vectored(
+        &mut self,
+        aad_parts: &[&[u8]],
+        cipher_txt: &[u8],
+    ) -> Result<Plaintext, HPKEError> {
+        self.check_open_limits(
+            aad_parts.iter().map(|part| part.len()).sum(),
+            cipher_txt.len(),
+        )?;
+        let ptxt = self.hpke.aead.open_incremental(
+            &self.key,
+            &self.compute_nonce()?,
+            &self.get_aad_parts(aad_parts),
+            cipher_txt,
+        )?;
+        self.increment_seq()?;
         Ok(ptxt)
     }
 
+    /// Seal `plain_txt` like `seal` with the aad given as `aad_parts`.
+    ///
+    /// The parts are concatenated, i.e. this is equivalent to `seal` with
+    /// `concat(aad_parts)` as aad. None of the AEAD backends accept the aad in
+    /// parts, so this saves the caller the concatenation but not the copy.
+    pub fn seal_incremental_aad(
+        &mut self,
+        aad_parts: &[&[u8]],
+        plain_txt: &[u8],
+    ) -> Result<Ciphertext, HPKEError> {
+        self.check_seal_limits(
+            aad_parts.iter().map(|part| part.len()).sum(),
+            plain_txt.len(),
+        )?;
+        let ctxt = self.hpke.aead.seal_incremental(
+            &self.key,
+            &self.compute_nonce()?,
+            &self.get_aad_parts(aad_parts),
+            plain_txt,
+        )?;
+        self.increment_seq()?;
+        Ok(ctxt)
+    }
+
+    /// Seal `plain_txt` like `seal` with an aad that consists of an
+    /// `explicit_aad` that is sent with the message and an `implicit_aad`
+    /// that the receiver knows from its own state, e.g. a connection id.
+    ///
+    /// The aad is built in a fixed order with the explicit part length
+    /// prefixed, so that both sides agree on it.
+    ///
+    /// ```text
+    /// aad = concat(I2OSP(len(explicit_aad), 8), explicit_aad, implicit_aad)
+    /// ```
+    ///
+    /// The receiver has to use `open_implicit_aad`.
+    pub fn seal_implicit_aad(
+        &mut self,
+        explicit_aad: &[u8],
+        implicit_aad: &[u8],
+        plain_txt: &[u8],
+    ) -> Result<Ciphertext, HPKEError> {
+        let explicit_len = (explicit_aad.len() as u64).to_be_bytes();
+        self.seal_incremental_aad(&[&explicit_len, explicit_aad, implicit_aad], plain_txt)
+    }
+
+    /// Open `cipher_txt` from `seal_implicit_aad` with the received
+    /// `explicit_aad` and the locally known `implicit_aad`.
+    pub fn open_implicit_aad(
+        &mut self,
+        explicit_aad: &[u8],
+        implicit_aad: &[u8],
+        cipher_txt: &[u8],
+    ) -> Result<Plaintext, HPKEError> {
+        let explicit_len = (explicit_aad.len() as u64).to_be_bytes();
+        self.open_vectored(&[&explicit_len, explicit_aad, implicit_aad], cipher_txt)
+    }
+
+    /// Seal all `parts` into a single ciphertext with one `seal`.
+    ///
+    /// Each part is prefixed with its length as four byte big-endian integer
+    /// so that `open_multi` can split them again.
+    ///
+    /// ```text
+    /// pt = concat(I2OSP(len(p_1), 4), p_1, ..., I2OSP(len(p_n), 4), p_n)
+    /// ```
+    ///
+    /// Returns an `InvalidInput` error if a part is longer than 2^32 - 1 bytes.
+    pub fn seal_multi(&mut self, aad: &[u8], parts: &[&[u8]]) -> Result<Ciphertext, HPKEError> {
+        let mut plain_txt = Vec::new();
+        for part in parts {
+            if part.len() > u32::MAX as usize {
+                return Err(HPKEError::InvalidInput);
+            }
+            plain_txt.extend_from_slice(&(part.len() as u32).to_be_bytes());
+            plain_txt.extend_from_slice(part);
+        }
+        self.seal(aad, &plain_txt)
+    }
+
+    /// Open `cipher_txt` from `seal_multi` and split it into its parts.
+    ///
+    /// Returns an `InvalidInput` error if the plain text isn't correctly
+    /// framed.
+    pub fn open_multi(
+        &mut self,
+        aad: &[u8],
+        cipher_txt: &[u8],
+    ) -> Result<Vec<Plaintext>, HPKEError> {
+        let plain_txt = self.open(aad, cipher_txt)?;
+        let mut parts = Vec::new();
+        let mut rest = &plain_txt[..];
+        while !rest.is_empty() {
+            let (len, tail) = util::split_at_checked(rest, 4).ok_or(HPKEError::InvalidInput)?;
+            let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+            let (part, tail) = util::split_at_checked(tail, len).ok_or(HPKEError::InvalidInput)?;
+            parts.push(part.to_vec());
+            rest = tail;
+        }
+        Ok(parts)
+    }
+
+    /// Seal `plain_txt` padded to `padded_len` bytes to hide its length.
+    ///
+    /// The plain text is prefixed with its length as four byte big-endian
+    /// integer and padded with zeros. All plain texts up to `padded_len` bytes
+    /// result in ciphertexts of the same length.
+    ///
+    /// ```text
+    /// pt = concat(I2OSP(len(plain_txt), 4), plain_txt,
+    ///             zeros(padded_len - len(plain_txt)))
+    /// ```
+    ///
+    /// Returns an `InvalidInput` error if `plain_txt` is longer than
+    /// `padded_len` or `padded_len` is longer than 2^32 - 1 bytes.
+    /// The receiver has to use `open_padded`.
+    pub fn seal_padded(
+        &mut self,
+        aad: &[u8],
+        plain_txt: &[u8],
+        padded_len: usize,
+    ) -> Result<Ciphertext, HPKEError> {
+        if plain_txt.len() > padded_len || padded_len > u32::MAX as usize {
+            return Err(HPKEError::InvalidInput);
+        }
+        let mut padded = Zeroizing::new(Vec::with_capacity(4 + padded_len));
+        padded.extend_from_slice(&(plain_txt.len() as u32).to_be_bytes());
+        padded.extend_from_slice(plain_txt);
+        padded.resize(4 + padded_len, 0);
+        self.seal(aad, &padded)
+    }
+
+    /// Open `cipher_txt` from `seal_padded` and strip the padding.
+    ///
+    /// Returns an `InvalidInput` error if the plain text isn't correctly
+    /// padded.
+    pub fn open_padded(&mut self, aad: &[u8], cipher_txt: &[u8]) -> Result<Plaintext, HPKEError> {
+        let padded = Zeroizing::new(self.open(aad, cipher_txt)?);
+        let (len, rest) = util::split_at_checked(&padded, 4).ok_or(HPKEError::InvalidInput)?;
+        let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+        let (plain_txt, padding) =
+            util::split_at_checked(rest, len).ok_or(HPKEError::InvalidInput)?;
+        if padding.iter().any(|&b| b != 0) {
+            return Err(HPKEError::InvalidInput);
+        }
+        Ok(plain_txt.to_vec())
+    }
+
+    /// Seal `plain_txt` like `seal` and prepend a commitment to the AEAD key.
+    ///
+    /// This is not part of the RFC and both peers have to use it.
+    /// AES-GCM and ChaCha20Poly1305 are not key-committing, i.e. a ciphertext
+    /// can be crafted that opens under more than one key. The `Nh` byte
+    /// commitment ties the ciphertext to the key of this context.
+    /// The key is used as input key material because it may be shorter than
+    /// the `Nh` bytes HKDF-Expand requires for its pseudorandom key.
+    ///
+    /// ```text
+    /// commitment = LabeledExtract("", "commit", key)
+    /// ct = concat(commitment, Context.Seal(aad, pt))
+    /// ```
+    ///
+    /// The receiver has to use `open_committing`.
+    pub fn seal_committing(
+        &mut self,
+        aad: &[u8],
+        plain_txt: &[u8],
+    ) -> Result<Ciphertext, HPKEError> {
+        let ctxt = self.seal(aad, plain_txt)?;
+        let mut committed = self.key_commitment();
+        committed.extend_from_slice(&ctxt);
+        Ok(committed)
+    }
+
+    /// Open `cipher_txt` from `seal_committing`.
+    ///
+    /// The commitment is checked in constant time before the ciphertext is
+    /// opened. Returns an `OpenError` if it doesn't match the key of this
+    /// context.
+    pub fn open_committing(
+        &mut self,
+        aad: &[u8],
+        cipher_txt: &[u8],
+    ) -> Result<Plaintext, HPKEError> {
+        use subtle::ConstantTimeEq;
+
+        let (commitment, ctxt) =
+            util::split_at_checked(cipher_txt, self.hpke.nh).ok_or(HPKEError::OpenError)?;
+        if !bool::from(self.key_commitment().ct_eq(commitment)) {
+            return Err(HPKEError::OpenError);
+        }
+        self.open(aad, ctxt)
+    }
+
+    /// Seal `plain_txt` like `seal` and write the ciphertext to `writer`.
+    pub fn seal_to_writer<W: std::io::Write>(
+        &mut self,
+        aad: &[u8],
+        plain_txt: &[u8],
+        writer: &mut W,
+    ) -> Result<(), HPKEError> {
+        let ctxt = self.seal(aad, plain_txt)?;
+        writer.write_all(&ctxt).map_err(|_| HPKEError::Io)
+    }
+
+    /// Seal a stream of `(aad, plain_txt)` frames like `seal`.
+    ///
+    /// Every item of the returned iterator advances the sequence number.
+    pub fn seal_iter<I>(&mut self, iter: I) -> SealIter<'_, 'a, I>
+    where
+        I: Iterator<Item = (Vec<u8>, Vec<u8>)>,
+    {
+        SealIter {
+            context: self,
+            iter,
+        }
+    }
+
+    /// Read a ciphertext from `reader` until EOF and open it like `open`.
+    pub fn open_from_reader<R: std::io::Read>(
+        &mut self,
+        aad: &[u8],
+        reader: &mut R,
+    ) -> Result<Plaintext, HPKEError> {
+        let mut cipher_txt = Vec::new();
+        reader
+            .read_to_end(&mut cipher_txt)
+            .map_err(|_| HPKEError::Io)?;
+        self.open(aad, &cipher_txt)
+    }
+
     /// 5.3. Secret Export
     ///
     /// Takes a serialised exporter context as byte slice and a length for the
@@ -224,156 +848,2013 @@ impl<'a> Context<'a> {
         )
     }
 
-    // TODO: not cool
-    fn compute_nonce(&self) -> Vec<u8> {
-        let seq = self.sequence_number.to_be_bytes();
-        let mut enc_seq = vec![0u8; self.nonce.len() - seq.len()];
-        enc_seq.append(&mut seq.to_vec());
-        util::xor_bytes(&enc_seq, &self.nonce)
+    /// Same as `export` but the output length is given by the array type.
+    ///
+    /// The output is identical to `export(exporter_context, N)`.
+    /// Returns an `InvalidInput` error if `N` is larger than `255 * Nh`.
+    pub fn export_array<const N: usize>(
+        &self,
+        exporter_context: &[u8],
+    ) -> Result<[u8; N], HPKEError> {
+        self.check_export_len(N)?;
+        let exported = Zeroizing::new(self.export(exporter_context, N));
+        let mut out = [0u8; N];
+        out.copy_from_slice(&exported);
+        Ok(out)
+    }
+
+    /// Check in constant time that the export for `exporter_context` with
+    /// length `expected.len()` is `expected`.
+    ///
+    /// This is meant for key confirmation with a value received from the peer.
+    /// Values shorter than `Nh` or longer than `255 * Nh` bytes are rejected,
+    /// so that a peer can't skip the confirmation with an empty or truncated
+    /// value.
+    pub fn verify_export(&self, exporter_context: &[u8], expected: &[u8]) -> bool {
+        use subtle::ConstantTimeEq;
+
+        if expected.len() < self.hpke.nh || self.check_export_len(expected.len()).is_err() {
+            return false;
+        }
+
+        let exported = Zeroizing::new(self.export(exporter_context, expected.len()));
+        exported.ct_eq(expected).into()
     }
 
-    fn increment_seq(&mut self) {
-        self.sequence_number += 1;
+    /// Get a reader that produces the export for `exporter_context` with
+    /// length `length`.
+    ///
+    /// The HKDF-Expand output is computed lazily, block by block, while
+    /// reading. The bytes read are identical to `export(exporter_context,
+    /// length)`. The length has to be known upfront because it is part of the
+    /// labeled info. The reader returns EOF after `length` bytes.
+    /// Returns an `InvalidInput` error if `length` is larger than `255 * Nh`.
+    pub fn export_reader(
+        &self,
+        exporter_context: &[u8],
+        length: usize,
+    ) -> Result<ExportReader<'_>, HPKEError> {
+        self.check_export_len(length)?;
+        Ok(ExportReader {
+            kdf: &self.hpke.kdf,
+            prk: &self.exporter_secret,
+            info: self.hpke.kdf.labeled_info(
+                &self.hpke.get_ciphersuite(),
+                "sec",
+                exporter_context,
+                length,
+            ),
+            block: Zeroizing::new(Vec::new()),
+            offset: 0,
+            counter: 0,
+            remaining: length,
+        })
     }
-}
 
-/// The HPKE configuration struct.
-/// This holds the configuration for HPKE but no state.
-/// To use HPKE first instantiate the configuration with
-/// `let hpke = Hpke::new(mode, kem_mode, kdf_mode, aead_mode)`.
-/// Now one can use the `hpke` configuration.
-#[derive(Debug)]
-#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
-pub struct Hpke {
-    mode: Mode,
-    kem_id: kem::Mode,
-    kdf_id: kdf::Mode,
-    aead_id: aead::Mode,
-    kem: kem::Kem,
-    kdf: kdf::Kdf,
-    aead: aead::Aead,
-    nk: usize,
-    nn: usize,
-    nh: usize,
-}
+    /// Same as `export` but the exporter secret is zeroized on drop.
+    pub fn export_zeroizing(&self, exporter_context: &[u8], length: usize) -> Zeroizing<Vec<u8>> {
+        Zeroizing::new(self.export(exporter_context, length))
+    }
 
-impl std::fmt::Display for Hpke {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(
-            f,
-            "{}_{}_{}_{}",
-            self.mode.to_string().to_lowercase(),
-            self.kem_id.to_string().to_lowercase(),
-            self.kdf_id.to_string().to_lowercase(),
-            self.aead_id.to_string().to_lowercase()
-        )
+    /// Export a key and base nonce for the AEAD `mode` and return a ready to
+    /// use `SymmetricContext`, e.g. to key a data channel.
+    ///
+    /// This exports `Nk + Nn` bytes for `mode` and uses the first `Nk` bytes as
+    /// key and the remaining `Nn` bytes as base nonce.
+    pub fn export_aead(
+        &self,
+        mode: aead::Mode,
+        exporter_context: &[u8],
+    ) -> Result<aead::SymmetricContext, HPKEError> {
+        let aead = aead::Aead::new(mode);
+        let exported = self.export_zeroizing(exporter_context, aead.get_nk() + aead.get_nn());
+        let (key, base_nonce) = exported.split_at(aead.get_nk());
+        Ok(aead::SymmetricContext::new(mode, key, base_nonce)?)
     }
-}
 
-impl Hpke {
-    /// Set up the configuration for HPKE.
-    pub fn new(mode: Mode, kem_id: kem::Mode, kdf_id: kdf::Mode, aead_id: aead::Mode) -> Self {
-        let kem = kem::Kem::new(kem_id);
-        let kdf = kdf::Kdf::new(kdf_id);
-        let aead = aead::Aead::new(aead_id);
-        Self {
-            mode,
-            kem_id,
-            kdf_id,
-            aead_id,
-            nk: aead.get_nk(),
-            nn: aead.get_nn(),
-            nh: kdf.get_nh(),
-            kem,
-            kdf,
-            aead,
+    /// Split this context into independent contexts for sending and receiving
+    /// on a full-duplex channel.
+    ///
+    /// This is not part of the RFC.
+    /// The keys for both directions are exported with `export_aead` with the
+    /// exporter contexts `"c2s"` (initiator to responder) and `"s2c"`
+    /// (responder to initiator). The initiator's `SendContext` matches the
+    /// responder's `RecvContext` and vice versa.
+    pub fn into_duplex(self, is_initiator: bool) -> (SendContext, RecvContext) {
+        let export = |label: &[u8]| {
+            self.export_aead(self.hpke.aead_id, label)
+                .expect("The exported key and nonce have the AEAD's lengths.")
+        };
+        let (send, recv) = if is_initiator {
+            (export(b"c2s"), export(b"s2c"))
+        } else {
+            (export(b"s2c"), export(b"c2s"))
+        };
+        (SendContext { context: send }, RecvContext { context: recv })
+    }
+
+    /// Get the length of the ciphertext `seal` produces for a plain text of
+    /// length `pt_len`.
+    pub fn expected_ciphertext_len(&self, pt_len: usize) -> usize {
+        self.hpke.expected_ciphertext_len(pt_len)
+    }
+
+    /// Get the number of messages that can still be sealed or opened with
+    /// this context before the nonce space is exhausted.
+    ///
+    /// This is `max_seq - sequence_number` where `max_seq` is
+    /// `(1 << (8 * Nn)) - 1`, bounded by the 32-bit sequence number of this
+    /// implementation.
+    pub fn remaining_messages(&self) -> u128 {
+        self.max_seq() - self.sequence_number as u128
+    }
+
+    /// Compute the nonce for the sequence number `seq` without sealing or
+    /// opening anything.
+    ///
+    /// This is `ComputeNonce(seq)` from the RFC and doesn't change the state
+    /// of the context, which is useful to debug nonce mismatches with other
+    /// implementations.
+    /// Returns an `InvalidInput` error if `seq` is larger than the maximum
+    /// sequence number of this context (see `remaining_messages`).
+    pub fn nonce_for_seq(&self, seq: u128) -> Result<Vec<u8>, HPKEError> {
+        if seq > self.max_seq() {
+            return Err(HPKEError::InvalidInput);
+        }
+        Ok(aead::compute_nonce(&self.nonce, seq as u32))
+    }
+
+    /// Get an `Nh` byte session binder for the `label`, e.g. to bind a
+    /// protocol transcript MAC to this session.
+    ///
+    /// This is not part of the RFC.
+    /// The binder is derived from the exporter secret with its own label, so
+    /// it is independent of all `export` outputs and doesn't reveal the key
+    /// schedule secrets.
+    ///
+    /// ```text
+    /// binder = LabeledExpand(exporter_secret, "binder", label, Nh)
+    /// ```
+    pub fn binder(&self, label: &[u8]) -> Vec<u8> {
+        self.hpke.kdf.labeled_expand(
+            &self.exporter_secret,
+            &self.hpke.get_ciphersuite(),
+            "binder",
+            label,
+            self.hpke.nh,
+        )
+    }
+
+    /// Get the non-secret metadata of this context for logging.
+    ///
+    /// The fingerprint identifies the session without revealing anything
+    /// about its secrets.
+    ///
+    /// ```text
+    /// fingerprint = LabeledExpand(exporter_secret, "fingerprint", "", 8)
+    /// ```
+    pub fn metadata(&self) -> ContextMetadata {
+        ContextMetadata {
+            mode: self.hpke.mode,
+            kem_id: self.hpke.kem_id,
+            kdf_id: self.hpke.kdf_id,
+            aead_id: self.hpke.aead_id,
+            sequence_number: self.sequence_number,
+            fingerprint: self.fingerprint(),
+        }
+    }
+
+    /// Derive `steps` message keys from a symmetric chain that is seeded with
+    /// the exporter secret.
+    ///
+    /// This is not part of the RFC.
+    /// Each step derives an `Nh` byte message key and the next chain key from
+    /// the current chain key. The chain keys are zeroized after use.
+    ///
+    /// ```text
+    /// chain_key_0 = exporter_secret
+    /// msg_key_i = LabeledExpand(chain_key_i, "msg", "", Nh)
+    /// chain_key_(i+1) = LabeledExpand(chain_key_i, "chain", "", Nh)
+    /// ```
+    pub fn ratchet_chain(&self, steps: usize) -> Vec<Vec<u8>> {
+        let suite_id = self.hpke.get_ciphersuite();
+        let kdf = &self.hpke.kdf;
+        let mut chain_key = Zeroizing::new(self.exporter_secret.clone());
+        let mut message_keys = Vec::with_capacity(steps);
+        for _ in 0..steps {
+            message_keys.push(kdf.labeled_expand(&chain_key, &suite_id, "msg", b"", self.hpke.nh));
+            chain_key = Zeroizing::new(kdf.labeled_expand(
+                &chain_key,
+                &suite_id,
+                "chain",
+                b"",
+                self.hpke.nh,
+            ));
+        }
+        message_keys
+    }
+
+    /// Derive a deterministic `length` byte IV for the `message_number`, e.g.
+    /// for keying a non-AEAD primitive like AES-CTR with an export.
+    ///
+    /// This is not part of the RFC and only meant for interop with non-AEAD
+    /// systems. Each message number yields a different IV, so the caller has
+    /// to use every message number only once.
+    /// Returns an `InvalidInput` error if `length` is larger than `255 * Nh`.
+    ///
+    /// ```text
+    /// iv = LabeledExpand(exporter_secret, "iv", I2OSP(message_number, 8), L)
+    /// ```
+    pub fn export_iv(&self, message_number: u64, length: usize) -> Result<Vec<u8>, HPKEError> {
+        self.check_export_len(length)?;
+        Ok(self.hpke.kdf.labeled_expand(
+            &self.exporter_secret,
+            &self.hpke.get_ciphersuite(),
+            "iv",
+            &message_number.to_be_bytes(),
+            length,
+        ))
+    }
+
+    /// Export an `Nh` byte HMAC key for the `label`, e.g. for protocols that
+    /// use HPKE only for key agreement and do their own encrypt-then-MAC.
+    ///
+    /// This is not part of the RFC.
+    /// Use `compute_mac` and `verify_mac` to use the key with the hash of the
+    /// suite's KDF.
+    ///
+    /// ```text
+    /// mac_key = LabeledExpand(exporter_secret, "mac_key", label, Nh)
+    /// ```
+    pub fn export_mac_key(&self, label: &[u8]) -> Vec<u8> {
+        self.hpke.kdf.labeled_expand(
+            &self.exporter_secret,
+            &self.hpke.get_ciphersuite(),
+            "mac_key",
+            label,
+            self.hpke.nh,
+        )
+    }
+
+    /// Compute the HMAC tag of `data` under the `key` with the hash of the
+    /// suite's KDF.
+    pub fn compute_mac(&self, key: &[u8], data: &[u8]) -> Vec<u8> {
+        self.hpke.kdf.hmac(key, data)
+    }
+
+    /// Verify the HMAC `tag` of `data` under the `key` with the hash of the
+    /// suite's KDF.
+    ///
+    /// The comparison is constant time.
+    pub fn verify_mac(&self, key: &[u8], data: &[u8], tag: &[u8]) -> bool {
+        use subtle::ConstantTimeEq;
+
+        self.compute_mac(key, data).ct_eq(tag).into()
+    }
+
+    /// Export a secret that is bound to a `transcript`, e.g. the message aad.
+    ///
+    /// This is a convention on top of the RFC export, not a replacement.
+    /// It uses `export_labeled` with the protocol label `"transcript"` and
+    /// prepends the length-prefixed transcript to the exporter context.
+    /// Returns an `InvalidInput` error if the transcript isn't shorter than
+    /// 2^32 bytes or `length` is larger than `255 * Nh`.
+    ///
+    /// ```text
+    /// def Context.ExportBound(transcript, exporter_context, L):
+    ///   context = concat(I2OSP(len(transcript), 4), transcript,
+    ///                    exporter_context)
+    ///   return Context.ExportLabeled("transcript", context, L)
+    /// ```
+    pub fn export_bound(
+        &self,
+        transcript: &[u8],
+        exporter_context: &[u8],
+        length: usize,
+    ) -> Result<Vec<u8>, HPKEError> {
+        if transcript.len() > u32::MAX as usize {
+            return Err(HPKEError::InvalidInput);
+        }
+        let transcript_len = (transcript.len() as u32).to_be_bytes();
+        self.export_labeled(
+            b"transcript",
+            &util::concat(&[&transcript_len, transcript, exporter_context]),
+            length,
+        )
+    }
+
+    /// Export a secret that is domain separated by a `protocol_label`.
+    ///
+    /// This is a convention on top of the RFC export, not a replacement.
+    /// The length-prefixed protocol label is prepended to the exporter context.
+    /// Returns an `InvalidInput` error if the protocol label isn't shorter than
+    /// 2^16 bytes or `length` is larger than `255 * Nh`.
+    ///
+    /// ```text
+    /// def Context.ExportLabeled(protocol_label, exporter_context, L):
+    ///   context = concat(I2OSP(len(protocol_label), 2), protocol_label,
+    ///                    exporter_context)
+    ///   return Context.Export(context, L)
+    /// ```
+    pub fn export_labeled(
+        &self,
+        protocol_label: &[u8],
+        exporter_context: &[u8],
+        length: usize,
+    ) -> Result<Vec<u8>, HPKEError> {
+        if protocol_label.len() > u16::MAX as usize {
+            return Err(HPKEError::InvalidInput);
+        }
+        self.check_export_len(length)?;
+        let label_len = (protocol_label.len() as u16).to_be_bytes();
+        Ok(self.export(
+            &util::concat(&[&label_len, protocol_label, exporter_context]),
+            length,
+        ))
+    }
+
+    /// Ratchet the context forward.
+    ///
+    /// This is not part of the RFC.
+    /// A new key, base nonce, and exporter secret are derived from the current
+    /// exporter secret, the old values are zeroized, and the sequence number is
+    /// reset to 0. Both parties have to ratchet in lockstep.
+    ///
+    /// ```text
+    /// key = LabeledExpand(exporter_secret, "ratchet", "key", Nk)
+    /// base_nonce = LabeledExpand(exporter_secret, "ratchet", "base_nonce", Nn)
+    /// exporter_secret = LabeledExpand(exporter_secret, "ratchet", "exp", Nh)
+    /// ```
+    pub fn ratchet(&mut self) -> Result<(), HPKEError> {
+        let suite_id = self.hpke.get_ciphersuite();
+        let kdf = &self.hpke.kdf;
+        let key = kdf.labeled_expand(
+            &self.exporter_secret,
+            &suite_id,
+            "ratchet",
+            b"key",
+            self.hpke.nk,
+        );
+        let nonce = kdf.labeled_expand(
+            &self.exporter_secret,
+            &suite_id,
+            "ratchet",
+            b"base_nonce",
+            self.hpke.nn,
+        );
+        let exporter_secret = kdf.labeled_expand(
+            &self.exporter_secret,
+            &suite_id,
+            "ratchet",
+            b"exp",
+            self.hpke.nh,
+        );
+
+        self.key.zeroize();
+        self.nonce.zeroize();
+        self.exporter_secret.zeroize();
+        self.key = key;
+        self.nonce = nonce;
+        self.exporter_secret = exporter_secret;
+        self.sequence_number = 0;
+        Ok(())
+    }
+
+    /// Derive a new AEAD key and base nonce from the exporter secret.
+    ///
+    /// This is not part of the RFC.
+    /// The old key and nonce are zeroized and the sequence number is reset to
+    /// 0. The exporter secret is not changed such that exports stay stable.
+    /// Both parties have to refresh with the same `label` in lockstep.
+    ///
+    /// Each refresh increments an epoch counter of the context that is mixed
+    /// into the derivation. Because the sequence number starts at 0 again,
+    /// refreshing twice with the same `label` must not bring back a key and
+    /// nonce that were already used.
+    /// Returns a `MessageLimitReached` error if the epoch counter is exhausted.
+    ///
+    /// ```text
+    /// epoch = epoch + 1
+    /// refresh_info = concat(I2OSP(epoch, 8), label)
+    /// key = LabeledExpand(exporter_secret, "refresh_key", refresh_info, Nk)
+    /// base_nonce = LabeledExpand(exporter_secret, "refresh_nonce", refresh_info, Nn)
+    /// ```
+    pub fn refresh_aead_key(&mut self, label: &[u8]) -> Result<(), HPKEError> {
+        let epoch = self
+            .refresh_epoch
+            .checked_add(1)
+            .ok_or(HPKEError::MessageLimitReached)?;
+        let suite_id = self.hpke.get_ciphersuite();
+        let mut outputs = self
+            .hpke
+            .kdf
+            .labeled_expand_multi(
+                &self.exporter_secret,
+                &suite_id,
+                &util::concat(&[&epoch.to_be_bytes(), label]),
+                &[
+                    ("refresh_key", self.hpke.nk),
+                    ("refresh_nonce", self.hpke.nn),
+                ],
+            )
+            .into_iter();
+
+        self.key.zeroize();
+        self.nonce.zeroize();
+        // Unwrapping here is fine because there's one output per label.
+        self.key = outputs.next().unwrap();
+        self.nonce = outputs.next().unwrap();
+        self.sequence_number = 0;
+        self.refresh_epoch = epoch;
+        Ok(())
+    }
+
+    /// Get the aad to use for the AEAD, i.e. `aad` with the context's aad
+    /// prefix prepended.
+    /// The prefix is stored with its length prefix, see
+    /// `Hpke::with_aad_prefix`.
+    #[inline]
+    fn get_aad<'b>(&self, aad: &'b [u8]) -> std::borrow::Cow<'b, [u8]> {
+        if self.aad_prefix.is_empty() {
+            std::borrow::Cow::Borrowed(aad)
+        } else {
+            std::borrow::Cow::Owned(util::concat(&[&self.aad_prefix, aad]))
+        }
+    }
+
+    /// Get the aad parts to use for the AEAD, i.e. `aad_parts` with the
+    /// context's length-prefixed aad prefix prepended.
+    #[inline]
+    fn get_aad_parts<'b>(&'b self, aad_parts: &[&'b [u8]]) -> Vec<&'b [u8]> {
+        let mut parts = Vec::with_capacity(aad_parts.len() + 1);
+        if !self.aad_prefix.is_empty() {
+            parts.push(&self.aad_prefix[..]);
+        }
+        parts.extend_from_slice(aad_parts);
+        parts
+    }
+
+    /// Fingerprint of the session for `metadata`.
+    fn fingerprint(&self) -> [u8; 8] {
+        let fingerprint = self.hpke.kdf.labeled_expand(
+            &self.exporter_secret,
+            &self.hpke.get_ciphersuite(),
+            "fingerprint",
+            b"",
+            8,
+        );
+        let mut out = [0u8; 8];
+        out.copy_from_slice(&fingerprint);
+        out
+    }
+
+    /// Commitment to the AEAD key for `seal_committing` and `open_committing`.
+    fn key_commitment(&self) -> Vec<u8> {
+        self.hpke
+            .kdf
+            .labeled_extract(&[], &self.hpke.get_ciphersuite(), "commit", &self.key)
+    }
+
+    /// The largest sequence number, i.e. `(1 << (8 * Nn)) - 1` bounded by the
+    /// 32-bit sequence number of this implementation.
+    fn max_seq(&self) -> u128 {
+        let max_seq = if self.hpke.nn >= 16 {
+            u128::MAX
+        } else {
+            (1u128 << (8 * self.hpke.nn)) - 1
+        };
+        max_seq.min(u32::MAX as u128)
+    }
+
+    /// Check that `length` bytes can be exported, i.e. that `length` is at
+    /// most `255 * Nh`.
+    fn check_export_len(&self, length: usize) -> Result<(), HPKEError> {
+        if length > 255 * self.hpke.nh {
+            return Err(HPKEError::InvalidInput);
+        }
+        Ok(())
+    }
+
+    /// Check the aad and plain text lengths against the `HpkeLimits`.
+    fn check_limits(&self, aad_len: usize, plain_txt_len: usize) -> Result<(), HPKEError> {
+        let limits = &self.hpke.limits;
+        if aad_len > limits.max_aad || plain_txt_len > limits.max_plaintext {
+            return Err(HPKEError::InvalidInput);
+        }
+        Ok(())
+    }
+
+    /// Check the aad and plain text lengths, and the resulting ciphertext
+    /// length against the `HpkeLimits`.
+    fn check_seal_limits(&self, aad_len: usize, plain_txt_len: usize) -> Result<(), HPKEError> {
+        self.check_limits(aad_len, plain_txt_len)?;
+        if plain_txt_len.saturating_add(self.hpke.aead.get_nt()) > self.hpke.limits.max_seal_output
+        {
+            return Err(HPKEError::InvalidInput);
+        }
+        Ok(())
+    }
+
+    /// Check the aad and ciphertext lengths against the `HpkeLimits`.
+    fn check_open_limits(&self, aad_len: usize, cipher_txt_len: usize) -> Result<(), HPKEError> {
+        if cipher_txt_len > self.hpke.limits.max_open_input {
+            return Err(HPKEError::InvalidInput);
+        }
+        self.check_limits(
+            aad_len,
+            cipher_txt_len.saturating_sub(self.hpke.aead.get_nt()),
+        )
+    }
+
+    // TODO: not cool
+    fn compute_nonce(&self) -> Result<Vec<u8>, HPKEError> {
+        if self.external_counter {
+            return Err(HPKEError::InvalidConfig);
+        }
+        Ok(aead::compute_nonce(&self.nonce, self.sequence_number))
+    }
+
+    /// ```text
+    /// def Context.IncrementSeq():
+    ///   if self.seq >= (1 << (8*Nn)) - 1:
+    ///     raise MessageLimitReachedError
+    ///   self.seq += 1
+    /// ```
+    ///
+    /// The sequence number is bounded by its 32 bit width here.
+    fn increment_seq(&mut self) -> Result<(), HPKEError> {
+        self.sequence_number = self
+            .sequence_number
+            .checked_add(1)
+            .ok_or(HPKEError::MessageLimitReached)?;
+        Ok(())
+    }
+}
+
+/// The sending half of a full-duplex channel, see `Context::into_duplex`.
+#[derive(Debug)]
+pub struct SendContext {
+    context: aead::SymmetricContext,
+}
+
+impl SendContext {
+    /// Seal `plain_txt` with `aad` for the peer's `RecvContext`.
+    pub fn seal(&mut self, aad: &[u8], plain_txt: &[u8]) -> Result<Ciphertext, HPKEError> {
+        Ok(self.context.seal(aad, plain_txt)?)
+    }
+}
+
+/// The receiving half of a full-duplex channel, see `Context::into_duplex`.
+#[derive(Debug)]
+pub struct RecvContext {
+    context: aead::SymmetricContext,
+}
+
+impl RecvContext {
+    /// Open `cipher_txt` with `aad` from the peer's `SendContext`.
+    pub fn open(&mut self, aad: &[u8], cipher_txt: &[u8]) -> Result<Plaintext, HPKEError> {
+        Ok(self.context.open(aad, cipher_txt)?)
+    }
+}
+
+/// An HPKE context that uses a fresh random nonce for every message instead
+/// of the sequence number.
+///
+/// This is not part of the RFC.
+/// The random nonce is prepended to the ciphertext. The wrapped context's
+/// sequence number isn't used.
+/// Random nonces are only safe for AEADs with wide nonces or a small number of
+/// messages; with 12 byte nonces no more than 2^32 messages should be sealed.
+/// Both peers have to use a `RandomNonceContext`.
+#[derive(Debug)]
+pub struct RandomNonceContext<'a> {
+    context: Context<'a>,
+}
+
+impl<'a> RandomNonceContext<'a> {
+    /// Switch `context` to random nonces.
+    pub fn new(context: Context<'a>) -> Self {
+        Self { context }
+    }
+
+    /// Seal `plain_txt` with a random nonce.
+    ///
+    /// Returns `concat(nonce, ct)`.
+    pub fn seal(&self, aad: &[u8], plain_txt: &[u8]) -> Result<Ciphertext, HPKEError> {
+        let context = &self.context;
+        context.check_seal_limits(aad.len(), plain_txt.len())?;
+        let nonce = context.hpke.random_vec(context.hpke.nn);
+        let ctxt =
+            context
+                .hpke
+                .aead
+                .seal(&context.key, &nonce, &context.get_aad(aad), plain_txt)?;
+        Ok(util::concat(&[&nonce, &ctxt]))
+    }
+
+    /// Open `cipher_txt`, i.e. `concat(nonce, ct)`, from `seal`.
+    pub fn open(&self, aad: &[u8], cipher_txt: &[u8]) -> Result<Plaintext, HPKEError> {
+        let context = &self.context;
+        let (nonce, ctxt) =
+            util::split_at_checked(cipher_txt, context.hpke.nn).ok_or(HPKEError::OpenError)?;
+        context.check_open_limits(aad.len(), ctxt.len())?;
+        let ptxt = context
+            .hpke
+            .aead
+            .open(&context.key, nonce, &context.get_aad(aad), ctxt)?;
+        Ok(ptxt)
+    }
+
+    /// Get a reference to the wrapped context, e.g. to export secrets.
+    pub fn context(&self) -> &Context<'a> {
+        &self.context
+    }
+}
+
+/// An HPKE context with the AEAD `A` selected at compile time.
+///
+/// This wraps a `Context` and calls the AEAD without dynamic dispatch.
+/// The results are identical to the wrapped `Context`.
+#[derive(Debug)]
+pub struct TypedContext<'a, A: aead::AeadTrait> {
+    context: Context<'a>,
+    aead: A,
+}
+
+impl<'a, A: aead::AeadTrait> TypedContext<'a, A> {
+    /// Wrap `context` into a typed context.
+    ///
+    /// Returns an `InvalidConfig` error if the context's AEAD is not `A`.
+    pub fn new(context: Context<'a>) -> Result<Self, HPKEError> {
+        let aead = A::new();
+        if aead.get_mode() != context.hpke.aead_id {
+            return Err(HPKEError::InvalidConfig);
+        }
+        Ok(Self { context, aead })
+    }
+
+    /// Seal `plain_txt` like `Context::seal`.
+    #[inline]
+    pub fn seal(&mut self, aad: &[u8], plain_txt: &[u8]) -> Result<Ciphertext, HPKEError> {
+        let context = &mut self.context;
+        context.check_seal_limits(aad.len(), plain_txt.len())?;
+        let ctxt = self.aead.seal(
+            &context.key,
+            &context.compute_nonce()?,
+            &context.get_aad(aad),
+            plain_txt,
+        )?;
+        context.increment_seq()?;
+        Ok(ctxt)
+    }
+
+    /// Open `cipher_txt` like `Context::open`.
+    #[inline]
+    pub fn open(&mut self, aad: &[u8], cipher_txt: &[u8]) -> Result<Plaintext, HPKEError> {
+        let context = &mut self.context;
+        context.check_open_limits(aad.len(), cipher_txt.len())?;
+        let ptxt = self.aead.open(
+            &context.key,
+            &context.compute_nonce()?,
+            &context.get_aad(aad),
+            cipher_txt,
+        )?;
+        context.increment_seq()?;
+        Ok(ptxt)
+    }
+
+    /// Get a reference to the wrapped context, e.g. to export secrets.
+    pub fn context(&self) -> &Context<'a> {
+        &self.context
+    }
+
+    /// Unwrap the context.
+    pub fn into_inner(self) -> Context<'a> {
+        self.context
+    }
+}
+
+/// An iterator sealing plain text frames, see `Context::seal_iter`.
+#[derive(Debug)]
+pub struct SealIter<'c, 'a, I> {
+    context: &'c mut Context<'a>,
+    iter: I,
+}
+
+impl<'c, 'a, I> Iterator for SealIter<'c, 'a, I>
+where
+    I: Iterator<Item = (Vec<u8>, Vec<u8>)>,
+{
+    type Item = Result<Ciphertext, HPKEError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (aad, plain_txt) = self.iter.next()?;
+        Some(self.context.seal(&aad, &plain_txt))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+/// A reader for a lazily computed export, see `Context::export_reader`.
+pub struct ExportReader<'a> {
+    kdf: &'a kdf::Kdf,
+    prk: &'a [u8],
+    info: Vec<u8>,
+    block: Zeroizing<Vec<u8>>,
+    offset: usize,
+    counter: u8,
+    remaining: usize,
+}
+
+impl<'a> std::fmt::Debug for ExportReader<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ExportReader {{ remaining: {} }}", self.remaining)
+    }
+}
+
+impl<'a> std::io::Read for ExportReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut written = 0;
+        while written < buf.len() && self.remaining > 0 {
+            if self.offset == self.block.len() {
+                // The length check in `export_reader` ensures that this
+                // doesn't overflow.
+                self.counter += 1;
+                let block = self
+                    .kdf
+                    .expand_block(self.prk, &self.block, &self.info, self.counter);
+                self.block.zeroize();
+                *self.block = block;
+                self.offset = 0;
+            }
+            let n = (buf.len() - written)
+                .min(self.block.len() - self.offset)
+                .min(self.remaining);
+            buf[written..written + n].copy_from_slice(&self.block[self.offset..self.offset + n]);
+            written += n;
+            self.offset += n;
+            self.remaining -= n;
+        }
+        Ok(written)
+    }
+}
+
+/// The HPKE configuration struct.
+/// This holds the configuration for HPKE but no state.
+/// To use HPKE first instantiate the configuration with
+/// `let hpke = Hpke::new(mode, kem_mode, kdf_mode, aead_mode)`.
+/// Now one can use the `hpke` configuration.
+///
+/// `Hpke` is `Send` and `Sync`, i.e. one configuration can be shared between
+/// threads, e.g. in an `Arc`, to set up contexts concurrently.
+/// A `Context` is `Send` and `Sync` as well but `seal` and `open` need
+/// exclusive access.
+#[derive(Debug)]
+pub struct Hpke {
+    mode: Mode,
+    kem_id: kem::Mode,
+    kdf_id: kdf::Mode,
+    aead_id: aead::Mode,
+    kem: kem::Kem,
+    kdf: kdf::Kdf,
+    aead: aead::Aead,
+    nk: usize,
+    nn: usize,
+    nh: usize,
+    rng: Option<Rng>,
+    limits: HpkeLimits,
+    aad_prefix: Vec<u8>,
+    assume_valid_psk: bool,
+}
+
+/// Length limits for untrusted HPKE inputs.
+///
+/// All limits are in bytes. The setup functions check `info` and `psk`, and
+/// the context's seal and open functions check the aad, plain text, seal
+/// output, and open input.
+/// Violations result in an `InvalidInput` error.
+/// By default there are no limits.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HpkeLimits {
+    /// The maximum length of the `info`.
+    pub max_info: usize,
+    /// The maximum length of the aad.
+    pub max_aad: usize,
+    /// The maximum length of the PSK.
+    pub max_psk: usize,
+    /// The maximum length of a plain text.
+    pub max_plaintext: usize,
+    /// The maximum length of a ciphertext produced by `seal`, i.e. the plain
+    /// text length plus `Nt`.
+    /// This is checked before the output is allocated.
+    pub max_seal_output: usize,
+    /// The maximum length of a ciphertext passed to `open`.
+    /// This is checked before anything is decrypted or allocated.
+    pub max_open_input: usize,
+}
+
+impl Default for HpkeLimits {
+    fn default() -> Self {
+        Self {
+            max_info: usize::MAX,
+            max_aad: usize::MAX,
+            max_psk: usize::MAX,
+            max_plaintext: usize::MAX,
+            max_seal_output: usize::MAX,
+            max_open_input: usize::MAX,
+        }
+    }
+}
+
+/// A caller provided randomness source for an `Hpke` configuration.
+struct Rng(std::sync::Mutex<Box<dyn CryptoRngCore + Send>>);
+
+impl std::fmt::Debug for Rng {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Rng")
+    }
+}
+
+/// The serialized form of an `Hpke` configuration.
+#[cfg(feature = "serialization")]
+#[derive(Serialize, Deserialize)]
+struct HpkeConfig {
+    mode: Mode,
+    kem_id: kem::Mode,
+    kdf_id: kdf::Mode,
+    aead_id: aead::Mode,
+}
+
+/// Only the mode and ciphersuite are serialized.
+#[cfg(feature = "serialization")]
+impl Serialize for Hpke {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::ser::Serializer,
+    {
+        HpkeConfig {
+            mode: self.mode,
+            kem_id: self.kem_id,
+            kdf_id: self.kdf_id,
+            aead_id: self.aead_id,
+        }
+        .serialize(serializer)
+    }
+}
+
+/// The configuration is rebuilt with `Hpke::try_new` from the mode and ciphersuite.
+#[cfg(feature = "serialization")]
+impl<'de> Deserialize<'de> for Hpke {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let config = HpkeConfig::deserialize(deserializer)?;
+        Self::try_new(config.mode, config.kem_id, config.kdf_id, config.aead_id)
+            .map_err(|_| serde::de::Error::custom("unsupported HPKE suite"))
+    }
+}
+
+impl std::fmt::Display for Hpke {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}_{}_{}_{}",
+            self.mode.to_string().to_lowercase(),
+            self.kem_id.to_string().to_lowercase(),
+            self.kdf_id.to_string().to_lowercase(),
+            self.aead_id.to_string().to_lowercase()
+        )
+    }
+}
+
+impl Hpke {
+    /// Set up the configuration for HPKE.
+    ///
+    /// Panics if the suite is not supported, see `try_new`.
+    pub fn new(mode: Mode, kem_id: kem::Mode, kdf_id: kdf::Mode, aead_id: aead::Mode) -> Self {
+        match Self::try_new(mode, kem_id, kdf_id, aead_id) {
+            Ok(hpke) => hpke,
+            Err(_) => panic!(
+                "The suite {:?}, {:?}, {:?} is not supported",
+                kem_id, kdf_id, aead_id
+            ),
+        }
+    }
+
+    /// Set up the configuration for HPKE.
+    ///
+    /// Returns an `UnsupportedSuite` error if the KEM isn't implemented by
+    /// this build.
+    /// This implementation also doesn't support suites where the KDF's hash
+    /// length `Nh` is shorter than the KEM's shared secret length `Nsecret`,
+    /// which is a policy of this crate and not an RFC requirement.
+    pub fn try_new(
+        mode: Mode,
+        kem_id: kem::Mode,
+        kdf_id: kdf::Mode,
+        aead_id: aead::Mode,
+    ) -> Result<Self, HPKEError> {
+        if !kem::SUPPORTED_MODES.contains(&kem_id) {
+            return Err(HPKEError::UnsupportedSuite);
+        }
+        let kem = kem::Kem::new(kem_id);
+        let kdf = kdf::Kdf::new(kdf_id);
+        if kdf.get_nh() < kem.shared_secret_len() {
+            return Err(HPKEError::UnsupportedSuite);
+        }
+        let aead = aead::Aead::new(aead_id);
+        Ok(Self {
+            mode,
+            kem_id,
+            kdf_id,
+            aead_id,
+            nk: aead.get_nk(),
+            nn: aead.get_nn(),
+            nh: kdf.get_nh(),
+            kem,
+            kdf,
+            aead,
+            rng: None,
+            limits: HpkeLimits::default(),
+            aad_prefix: Vec::new(),
+            assume_valid_psk: false,
+        })
+    }
+
+    /// Get the HPKE mode of this configuration.
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Get the KEM of this configuration.
+    pub fn kem_id(&self) -> kem::Mode {
+        self.kem_id
+    }
+
+    /// Get the KDF of this configuration.
+    pub fn kdf_id(&self) -> kdf::Mode {
+        self.kdf_id
+    }
+
+    /// Get the AEAD of this configuration.
+    pub fn aead_id(&self) -> aead::Mode {
+        self.aead_id
+    }
+
+    /// Prepend the domain separation `prefix` to the aad of every `seal` and
+    /// `open` call of contexts created with this configuration.
+    ///
+    /// This is not part of the RFC.
+    /// It keeps ciphertexts of sub-protocols that share a configuration and
+    /// keys apart. Both peers have to use the same prefix.
+    /// The prefix is length-prefixed so that different prefixes never result
+    /// in the same AEAD aad. An empty prefix leaves the aad unchanged.
+    ///
+    /// ```text
+    /// aad = concat(I2OSP(len(prefix), 8), prefix, aad)
+    /// ```
+    pub fn with_aad_prefix(mut self, prefix: Vec<u8>) -> Self {
+        self.aad_prefix = if prefix.is_empty() {
+            prefix
+        } else {
+            util::concat(&[&(prefix.len() as u64).to_be_bytes(), &prefix])
+        };
+        self
+    }
+
+    /// Skip the PSK input checks of the key schedule.
+    ///
+    /// **This is a footgun.** Only use it if the `psk` and `psk_id` inputs
+    /// are validated by the caller.
+    /// Without the checks, inconsistent, missing, or short PSKs, and PSKs in
+    /// the base and Auth modes are not rejected but go into the key schedule
+    /// as is. The result is not interoperable and may be insecure.
+    pub fn assume_valid_psk(mut self) -> Self {
+        self.assume_valid_psk = true;
+        self
+    }
+
+    /// Use the input length `limits` for this configuration.
+    pub fn with_limits(mut self, limits: HpkeLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Use `rng` as randomness source for `generate_key_pair` and the
+    /// ephemeral keys of `setup_sender`.
+    ///
+    /// Without this the OS randomness is used.
+    /// This is meant for deterministic tests and for external randomness
+    /// sources such as an HSM.
+    pub fn with_rng(mut self, rng: Box<dyn CryptoRngCore + Send>) -> Self {
+        self.rng = Some(Rng(std::sync::Mutex::new(rng)));
+        self
+    }
+
+    /// Get `len` random bytes from the configured randomness source.
+    fn random_vec(&self, len: usize) -> Vec<u8> {
+        match &self.rng {
+            Some(rng) => {
+                let mut out = vec![0u8; len];
+                rng.0
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .fill_bytes(&mut out);
+                out
+            }
+            None => random_bytes(len),
+        }
+    }
+
+    /// Get all ciphersuites `(kem, kdf, aead)` supported by this build.
+    pub fn supported_suites() -> Vec<(kem::Mode, kdf::Mode, aead::Mode)> {
+        let mut suites = Vec::new();
+        for &kem_id in kem::SUPPORTED_MODES.iter() {
+            for &kdf_id in kdf::SUPPORTED_MODES.iter() {
+                for &aead_id in aead::SUPPORTED_MODES.iter() {
+                    suites.push((kem_id, kdf_id, aead_id));
+                }
+            }
+        }
+        suites
+    }
+
+    /// Pick the first suite in `preference` that is supported by this build
+    /// and offered by the peer in `peer_suites`.
+    ///
+    /// The peer's suites are given as `(kem_id, kdf_id, aead_id)`.
+    /// Returns `None` if there's no common suite.
+    pub fn negotiate(
+        peer_suites: &[(u16, u16, u16)],
+        preference: &[(kem::Mode, kdf::Mode, aead::Mode)],
+    ) -> Option<(kem::Mode, kdf::Mode, aead::Mode)> {
+        let supported = Self::supported_suites();
+        preference.iter().copied().find(|suite| {
+            let (kem_id, kdf_id, aead_id) = *suite;
+            supported.contains(suite)
+                && peer_suites.contains(&(kem_id as u16, kdf_id as u16, aead_id as u16))
+        })
+    }
+
+    /// Get the length of the ciphertext `seal` produces for a plain text of
+    /// length `pt_len`.
+    pub fn expected_ciphertext_len(&self, pt_len: usize) -> usize {
+        pt_len + self.aead.get_nt()
+    }
+
+    /// Check that `enc` and `ct` are well-formed for this ciphersuite before
+    /// decrypting them.
+    ///
+    /// This only checks that `enc` is `Nenc` bytes long and that `ct` is at
+    /// least as long as the AEAD tag.
+    /// Returns an `InvalidInput` error if a check fails.
+    pub fn validate_message(&self, enc: &[u8], ct: &[u8]) -> Result<(), HPKEError> {
+        if enc.len() != self.kem.enc_len() || ct.len() < self.aead.get_nt() {
+            return Err(HPKEError::InvalidInput);
+        }
+        Ok(())
+    }
+
+    /// Get the encapsulated secret `enc` as public key.
+    ///
+    /// For DH based KEMs `enc` is the serialized ephemeral public key.
+    /// Returns an `InvalidInput` error if `enc` is not a valid public key and
+    /// an `InvalidConfig` error if the KEM is not DH based.
+    pub fn enc_to_public_key(&self, enc: &[u8]) -> Result<HPKEPublicKey, HPKEError> {
+        Ok(HPKEPublicKey::new(self.kem.enc_to_pk(enc)?))
+    }
+
+    /// Get a short identifier for the public key `pk`, e.g. as recipient hint
+    /// in a message header.
+    ///
+    /// This is not part of the RFC.
+    /// The id is scoped to this suite.
+    ///
+    /// ```text
+    /// key_id = LabeledExtract("", "key_id", pk)[..8]
+    /// ```
+    pub fn public_key_id(&self, pk: &HPKEPublicKey) -> [u8; 8] {
+        let prk = self
+            .kdf
+            .labeled_extract(&[], &self.get_ciphersuite(), "key_id", &pk.value);
+        let mut id = [0u8; 8];
+        id.copy_from_slice(&prk[..8]);
+        id
+    }
+
+    /// Split a framed message `enc || ct` into the encapsulated secret `enc`
+    /// and the ciphertext `ct`.
+    ///
+    /// The first `Nenc` bytes are the encapsulated secret.
+    /// Returns an `InvalidInput` error if `framed` is shorter than `Nenc`.
+    pub fn split_framed<'a>(&self, framed: &'a [u8]) -> Result<(&'a [u8], &'a [u8]), HPKEError> {
+        let enc_len = self.kem.enc_len();
+        if framed.len() < enc_len {
+            return Err(HPKEError::InvalidInput);
+        }
+        Ok(framed.split_at(enc_len))
+    }
+
+    /// Get the length of the KEM shared secret (`Nsecret`).
+    pub fn nsecret(&self) -> usize {
+        self.kem.shared_secret_len()
+    }
+
+    /// Get the length of the exporter secret of a context.
+    ///
+    /// The exporter secret is `Nh` bytes long, the output length of the
+    /// suite's KDF hash. `export` can produce up to `255 * Nh` bytes from it.
+    pub fn exporter_secret_len(&self) -> usize {
+        self.nh
+    }
+
+    /// Measure the average time of a base mode `setup_sender` and
+    /// `setup_receiver` pair for the suite of this configuration over
+    /// `iterations` runs.
+    ///
+    /// This is a rough estimate for capacity planning, not a replacement for
+    /// a benchmark harness. The time is dominated by the KEM operations.
+    /// Returns a zero duration if `iterations` is 0 and passes on errors of
+    /// the setup, e.g. if the KEM rejects the key pair.
+    #[cfg(feature = "bench")]
+    pub fn benchmark_setup(&self, iterations: usize) -> Result<std::time::Duration, HPKEError> {
+        let hpke = Hpke::new(Mode::Base, self.kem_id, self.kdf_id, self.aead_id);
+        let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+        let start = std::time::Instant::now();
+        for _ in 0..iterations {
+            let (enc, _sender_context) = hpke.setup_sender(&pk_r, &[], None, None, None)?;
+            hpke.setup_receiver(&enc, &sk_r, &[], None, None, None)?;
+        }
+        Ok(match iterations {
+            0 => std::time::Duration::ZERO,
+            _ => start.elapsed().div_f64(iterations as f64),
+        })
+    }
+
+    /// Set up an HPKE sender.
+    ///
+    /// For the base and PSK modes this encapsulates the public key `pk_r`
+    /// of the receiver.
+    /// For the Auth and AuthPSK modes this encapsulates and authenticates
+    /// the public key `pk_r` of the receiver with the senders secret key `sk_s`.
+    ///
+    /// The encapsulated secret is returned together with the context.
+    /// If the secret key is missing in an authenticated mode or has the wrong
+    /// length for the KEM, an `InvalidInput` error is returned.
+    ///
+    /// A `psk` of `Some(&[])` is the same as `None`, i.e. the RFC's empty
+    /// default PSK. Together with a non-empty `psk_id` it is an
+    /// `InconsistentPsk` error.
+    pub fn setup_sender(
+        &self,
+        pk_r: &HPKEPublicKey,
+        info: &[u8],
+        psk: Option<&[u8]>,
+        psk_id: Option<&[u8]>,
+        sk_s: Option<&HPKEPrivateKey>,
+    ) -> Result<(EncapsulatedSecret, Context), HPKEError> {
+        let (enc, _zz, context) =
+            self.setup_sender_with_shared_secret(pk_r, info, psk, psk_id, sk_s)?;
+        Ok((enc, context))
+    }
+
+    /// Set up an HPKE sender in the PSK or AuthPSK mode like `setup_sender`
+    /// with the `psk`.
+    pub fn setup_sender_psk(
+        &self,
+        pk_r: &HPKEPublicKey,
+        info: &[u8],
+        psk: &Psk,
+        sk_s: Option<&HPKEPrivateKey>,
+    ) -> Result<(EncapsulatedSecret, Context<'_>), HPKEError> {
+        self.setup_sender(pk_r, info, Some(&psk.secret), Some(&psk.id), sk_s)
+    }
+
+    /// Set up an HPKE sender like `setup_sender` and additionally return the
+    /// KEM shared secret.
+    ///
+    /// Returns the encapsulated secret, the shared secret, and the context.
+    pub fn setup_sender_with_shared_secret(
+        &self,
+        pk_r: &HPKEPublicKey,
+        info: &[u8],
+        psk: Option<&[u8]>,
+        psk_id: Option<&[u8]>,
+        sk_s: Option<&HPKEPrivateKey>,
+    ) -> Result<(EncapsulatedSecret, Vec<u8>, Context<'_>), HPKEError> {
+        let ikm_e = Zeroizing::new(self.random_vec(self.nsecret()));
+        let (zz, enc) = match self.mode {
+            Mode::Base | Mode::Psk => self.kem.encaps(&pk_r.value, &ikm_e),
+            Mode::Auth | Mode::AuthPsk => {
+                let sk_s = match sk_s {
+                    Some(s) if s.value.len() == self.kem.sk_len() => &s.value,
+                    _ => return Err(HPKEError::InvalidInput),
+                };
+                self.kem.auth_encaps(&pk_r.value, sk_s, &ikm_e)
+            }
+        }?;
+        let context = self.key_schedule(
+            &zz,
+            info,
+            psk.unwrap_or_default(),
+            psk_id.unwrap_or_default(),
+        )?;
+        Ok((enc, zz, context))
+    }
+
+    /// Set up an HPKE receiver.
+    ///
+    /// For the base and PSK modes this decapsulates `enc` with the secret key
+    /// `sk_r` of the receiver.
+    /// For the Auth and AuthPSK modes this decapsulates and authenticates `enc`
+    /// with the secret key `sk_r` of the receiver and the senders public key `pk_s`.
+    ///
+    /// The context based on the decapsulated values and, if present, the PSK is
+    /// returned.
+    /// If the sender's public key is missing in an authenticated mode or has the
+    /// wrong length for the KEM, an `InvalidInput` error is returned.
+    ///
+    /// A `psk` of `Some(&[])` is the same as `None`, i.e. the RFC's empty
+    /// default PSK. Together with a non-empty `psk_id` it is an
+    /// `InconsistentPsk` error.
+    pub fn setup_receiver(
+        &self,
+        enc: &[u8],
+        sk_r: &HPKEPrivateKey,
+        info: &[u8],
+        psk: Option<&[u8]>,
+        psk_id: Option<&[u8]>,
+        pk_s: Option<&HPKEPublicKey>,
+    ) -> Result<Context, HPKEError> {
+        let (_zz, context) =
+            self.setup_receiver_with_shared_secret(enc, sk_r, info, psk, psk_id, pk_s)?;
+        Ok(context)
+    }
+
+    /// 5.1.1. Encryption to a Public Key
+    ///
+    /// The RFC's `SetupBaseS`, i.e. `setup_sender` in the base mode.
+    /// Returns an `InvalidConfig` error if this isn't a base mode
+    /// configuration.
+    pub fn setup_base_sender(
+        &self,
+        pk_r: &HPKEPublicKey,
+        info: &[u8],
+    ) -> Result<(EncapsulatedSecret, Context<'_>), HPKEError> {
+        self.check_mode(Mode::Base)?;
+        self.setup_sender(pk_r, info, None, None, None)
+    }
+
+    /// 5.1.1. Encryption to a Public Key
+    ///
+    /// The RFC's `SetupBaseR`, i.e. `setup_receiver` in the base mode.
+    /// Returns an `InvalidConfig` error if this isn't a base mode
+    /// configuration.
+    pub fn setup_base_receiver(
+        &self,
+        enc: &[u8],
+        sk_r: &HPKEPrivateKey,
+        info: &[u8],
+    ) -> Result<Context<'_>, HPKEError> {
+        self.check_mode(Mode::Base)?;
+        self.setup_receiver(enc, sk_r, info, None, None, None)
+    }
+
+    /// 5.1.2. Authentication using a Pre-Shared Key
+    ///
+    /// The RFC's `SetupPSKS`, i.e. `setup_sender` in the PSK mode.
+    /// Returns an `InvalidConfig` error if this isn't a PSK mode
+    /// configuration.
+    pub fn setup_psk_sender(
+        &self,
+        pk_r: &HPKEPublicKey,
+        info: &[u8],
+        psk: &[u8],
+        psk_id: &[u8],
+    ) -> Result<(EncapsulatedSecret, Context<'_>), HPKEError> {
+        self.check_mode(Mode::Psk)?;
+        self.setup_sender(pk_r, info, Some(psk), Some(psk_id), None)
+    }
+
+    /// 5.1.2. Authentication using a Pre-Shared Key
+    ///
+    /// The RFC's `SetupPSKR`, i.e. `setup_receiver` in the PSK mode.
+    /// Returns an `InvalidConfig` error if this isn't a PSK mode
+    /// configuration.
+    pub fn setup_psk_receiver(
+        &self,
+        enc: &[u8],
+        sk_r: &HPKEPrivateKey,
+        info: &[u8],
+        psk: &[u8],
+        psk_id: &[u8],
+    ) -> Result<Context<'_>, HPKEError> {
+        self.check_mode(Mode::Psk)?;
+        self.setup_receiver(enc, sk_r, info, Some(psk), Some(psk_id), None)
+    }
+
+    /// 5.1.3. Authentication using an Asymmetric Key
+    ///
+    /// The RFC's `SetupAuthS`, i.e. `setup_sender` in the Auth mode.
+    /// Returns an `InvalidConfig` error if this isn't an Auth mode
+    /// configuration.
+    pub fn setup_auth_sender(
+        &self,
+        pk_r: &HPKEPublicKey,
+        info: &[u8],
+        sk_s: &HPKEPrivateKey,
+    ) -> Result<(EncapsulatedSecret, Context<'_>), HPKEError> {
+        self.check_mode(Mode::Auth)?;
+        self.setup_sender(pk_r, info, None, None, Some(sk_s))
+    }
+
+    /// 5.1.3. Authentication using an Asymmetric Key
+    ///
+    /// The RFC's `SetupAuthR`, i.e. `setup_receiver` in the Auth mode.
+    /// Returns an `InvalidConfig` error if this isn't an Auth mode
+    /// configuration.
+    pub fn setup_auth_receiver(
+        &self,
+        enc: &[u8],
+        sk_r: &HPKEPrivateKey,
+        info: &[u8],
+        pk_s: &HPKEPublicKey,
+    ) -> Result<Context<'_>, HPKEError> {
+        self.check_mode(Mode::Auth)?;
+        self.setup_receiver(enc, sk_r, info, None, None, Some(pk_s))
+    }
+
+    /// 5.1.4. Authentication using both a PSK and an Asymmetric Key
+    ///
+    /// The RFC's `SetupAuthPSKS`, i.e. `setup_sender` in the AuthPSK mode.
+    /// Returns an `InvalidConfig` error if this isn't an AuthPSK mode
+    /// configuration.
+    pub fn setup_auth_psk_sender(
+        &self,
+        pk_r: &HPKEPublicKey,
+        info: &[u8],
+        psk: &[u8],
+        psk_id: &[u8],
+        sk_s: &HPKEPrivateKey,
+    ) -> Result<(EncapsulatedSecret, Context<'_>), HPKEError> {
+        self.check_mode(Mode::AuthPsk)?;
+        self.setup_sender(pk_r, info, Some(psk), Some(psk_id), Some(sk_s))
+    }
+
+    /// 5.1.4. Authentication using both a PSK and an Asymmetric Key
+    ///
+    /// The RFC's `SetupAuthPSKR`, i.e. `setup_receiver` in the AuthPSK mode.
+    /// Returns an `InvalidConfig` error if this isn't an AuthPSK mode
+    /// configuration.
+    pub fn setup_auth_psk_receiver(
+        &self,
+        enc: &[u8],
+        sk_r: &HPKEPrivateKey,
+        info: &[u8],
+        psk: &[u8],
+        psk_id: &[u8],
+        pk_s: &HPKEPublicKey,
+    ) -> Result<Context<'_>, HPKEError> {
+        self.check_mode(Mode::AuthPsk)?;
+        self.setup_receiver(enc, sk_r, info, Some(psk), Some(psk_id), Some(pk_s))
+    }
+
+    /// Set up an HPKE context from a `shared_secret` that was established
+    /// without the KEM, e.g. out-of-band.
+    ///
+    /// This runs the key schedule like `setup_sender` and `setup_receiver`
+    /// after the KEM operation. Both peers get the same context, so use the
+    /// same `info` and PSK on both sides.
+    /// The PSK inputs are checked against the mode of this configuration.
+    pub fn import_shared_secret(
+        &self,
+        shared_secret: &[u8],
+        info: &[u8],
+        psk: Option<&[u8]>,
+        psk_id: Option<&[u8]>,
+    ) -> Result<Context<'_>, HPKEError> {
+        self.key_schedule(
+            shared_secret,
+            info,
+            psk.unwrap_or_default(),
+            psk_id.unwrap_or_default(),
+        )
+    }
+
+    /// Build an HPKE context directly from the output of a key schedule, i.e.
+    /// the AEAD `key`, the base `nonce`, and the `exporter_secret`.
+    ///
+    /// The sequence number starts at 0.
+    /// Returns an `InvalidConfig` error if the `nonce` isn't `Nn` bytes long
+    /// for the AEAD of this configuration, and an `InvalidInput` error if the
+    /// `key` or `exporter_secret` lengths don't match `Nk` and `Nh`.
+    pub fn context_from_parts(
+        &self,
+        key: Vec<u8>,
+        nonce: Vec<u8>,
+        exporter_secret: Vec<u8>,
+    ) -> Result<Context<'_>, HPKEError> {
+        if nonce.len() != self.nn {
+            return Err(HPKEError::InvalidConfig);
+        }
+        if key.len() != self.nk || exporter_secret.len() != self.nh {
+            return Err(HPKEError::InvalidInput);
+        }
+        Ok(Context {
+            key,
+            nonce,
+            exporter_secret,
+            sequence_number: 0,
+            external_counter: false,
+            refresh_epoch: 0,
+            aad_prefix: self.aad_prefix.clone(),
+            hpke: self,
+        })
+    }
+
+    /// Set up an HPKE receiver in the PSK or AuthPSK mode like
+    /// `setup_receiver` with the `psk`.
+    pub fn setup_receiver_psk(
+        &self,
+        enc: &[u8],
+        sk_r: &HPKEPrivateKey,
+        info: &[u8],
+        psk: &Psk,
+        pk_s: Option<&HPKEPublicKey>,
+    ) -> Result<Context<'_>, HPKEError> {
+        self.setup_receiver(enc, sk_r, info, Some(&psk.secret), Some(&psk.id), pk_s)
+    }
+
+    /// Set up an HPKE receiver like `setup_receiver` and additionally return
+    /// the KEM shared secret.
+    ///
+    /// Returns the shared secret and the context.
+    pub fn setup_receiver_with_shared_secret(
+        &self,
+        enc: &[u8],
+        sk_r: &HPKEPrivateKey,
+        info: &[u8],
+        psk: Option<&[u8]>,
+        psk_id: Option<&[u8]>,
+        pk_s: Option<&HPKEPublicKey>,
+    ) -> Result<(Vec<u8>, Context<'_>), HPKEError> {
+        let zz = match self.mode {
+            Mode::Base | Mode::Psk => self.kem.decaps(enc, &sk_r.value),
+            Mode::Auth | Mode::AuthPsk => {
+                let pk_s = match pk_s {
+                    Some(s) if s.value.len() == self.kem.pk_len() => &s.value,
+                    _ => return Err(HPKEError::InvalidInput),
+                };
+                self.kem.auth_decaps(enc, &sk_r.value, pk_s)
+            }
+        }?;
+        let context = self.key_schedule(
+            &zz,
+            info,
+            psk.unwrap_or_default(),
+            psk_id.unwrap_or_default(),
+        )?;
+        Ok((zz, context))
+    }
+
+    /// Set up an HPKE sender that encapsulates to two independent receiver
+    /// keys, `pk_r` for the KEM of this configuration and `pk_r_second` for
+    /// the KEM `second_kem`.
+    ///
+    /// This is not part of the RFC.
+    /// The session is secure as long as one of the two KEMs is.
+    /// Only the Base and PSK modes are supported.
+    ///
+    /// ```text
+    /// shared_secret_1, enc_1 = Encap(pk_r)
+    /// shared_secret_2, enc_2 = Encap_second(pk_r_second)
+    /// enc = concat(enc_1, enc_2)
+    /// shared_secret = LabeledExtract("", "dual_shared_secret",
+    ///     combine_shared_secrets(shared_secret_1, shared_secret_2, enc))
+    /// ```
+    ///
+    /// where `combine_shared_secrets` length prefixes every component.
+    ///
+    /// Returns the concatenated encapsulated secrets and the context.
+    /// Returns an `InvalidConfig` error in the Auth modes or if `second_kem`
+    /// is not supported.
+    pub fn setup_sender_dual(
+        &self,
+        second_kem: kem::Mode,
+        pk_r: &HPKEPublicKey,
+        pk_r_second: &HPKEPublicKey,
+        info: &[u8],
+        psk: Option<&[u8]>,
+        psk_id: Option<&[u8]>,
+    ) -> Result<(EncapsulatedSecret, Context<'_>), HPKEError> {
+        let second = self.get_second_kem(second_kem)?;
+        let ikm_e = Zeroizing::new(self.random_vec(self.nsecret()));
+        let (zz_1, enc_1) = self.kem.encaps(&pk_r.value, &ikm_e)?;
+        let zz_1 = Zeroizing::new(zz_1);
+        let ikm_e = Zeroizing::new(self.random_vec(second.shared_secret_len()));
+        let (zz_2, enc_2) = second.encaps(&pk_r_second.value, &ikm_e)?;
+        let zz_2 = Zeroizing::new(zz_2);
+
+        let enc = util::concat(&[&enc_1, &enc_2]);
+        let zz = Zeroizing::new(self.combine_dual_shared_secrets(&zz_1, &zz_2, &enc));
+        let context = self.key_schedule(
+            &zz,
+            info,
+            psk.unwrap_or_default(),
+            psk_id.unwrap_or_default(),
+        )?;
+        Ok((enc, context))
+    }
+
+    /// Set up an HPKE receiver for an encapsulated secret `enc` created with
+    /// `setup_sender_dual`.
+    ///
+    /// `sk_r` is the private key for the KEM of this configuration and
+    /// `sk_r_second` the one for `second_kem`.
+    /// Returns an `InvalidInput` error if `enc` has the wrong length and an
+    /// `InvalidConfig` error in the Auth modes or if `second_kem` is not
+    /// supported.
+    #[allow(clippy::too_many_arguments)]
+    pub fn setup_receiver_dual(
+        &self,
+        second_kem: kem::Mode,
+        enc: &[u8],
+        sk_r: &HPKEPrivateKey,
+        sk_r_second: &HPKEPrivateKey,
+        info: &[u8],
+        psk: Option<&[u8]>,
+        psk_id: Option<&[u8]>,
+    ) -> Result<Context<'_>, HPKEError> {
+        let second = self.get_second_kem(second_kem)?;
+        if enc.len() != self.kem.enc_len() + second.enc_len() {
+            return Err(HPKEError::InvalidInput);
+        }
+        let (enc_1, enc_2) = enc.split_at(self.kem.enc_len());
+        let zz_1 = Zeroizing::new(self.kem.decaps(enc_1, &sk_r.value)?);
+        let zz_2 = Zeroizing::new(second.decaps(enc_2, &sk_r_second.value)?);
+
+        let zz = Zeroizing::new(self.combine_dual_shared_secrets(&zz_1, &zz_2, enc));
+        self.key_schedule(
+            &zz,
+            info,
+            psk.unwrap_or_default(),
+            psk_id.unwrap_or_default(),
+        )
+    }
+
+    /// Get the second KEM for `setup_sender_dual` and `setup_receiver_dual`.
+    fn get_second_kem(&self, second_kem: kem::Mode) -> Result<kem::Kem, HPKEError> {
+        if self.mode == Mode::Auth
+            || self.mode == Mode::AuthPsk
+            || !kem::SUPPORTED_MODES.contains(&second_kem)
+        {
+            return Err(HPKEError::InvalidConfig);
+        }
+        Ok(kem::Kem::new(second_kem))
+    }
+
+    /// Combine the two shared secrets of a dual encapsulation.
+    fn combine_dual_shared_secrets(&self, zz_1: &[u8], zz_2: &[u8], enc: &[u8]) -> Vec<u8> {
+        let combined = Zeroizing::new(kem::combine_shared_secrets(&[zz_1, zz_2, enc]));
+        self.kdf.labeled_extract(
+            &[],
+            &self.get_ciphersuite(),
+            "dual_shared_secret",
+            &combined,
+        )
+    }
+
+    /// Rotate the PSK of a session by running the key schedule again with the
+    /// session's KEM `shared_secret` and the new PSK.
+    ///
+    /// This is not part of the RFC.
+    /// The shared secret is returned by `setup_sender_with_shared_secret` and
+    /// `setup_receiver_with_shared_secret`. Both parties have to rotate in
+    /// lockstep with the same `info`. The new context starts at sequence
+    /// number 0.
+    /// Returns an `InvalidConfig` error if the mode doesn't use a PSK.
+    pub fn rekey_with_psk(
+        &self,
+        shared_secret: &[u8],
+        info: &[u8],
+        new_psk: &[u8],
+        new_psk_id: &[u8],
+    ) -> Result<Context<'_>, HPKEError> {
+        if self.mode != Mode::Psk && self.mode != Mode::AuthPsk {
+            return Err(HPKEError::InvalidConfig);
+        }
+        self.key_schedule(shared_secret, info, new_psk, new_psk_id)
+    }
+
+    /// Set up an HPKE sender like `setup_sender` with the `info` computed by
+    /// finalizing the `transcript` hash.
+    ///
+    /// Returns an `InvalidInput` error if the output size of the digest `D`
+    /// isn't the `Nh` of the KDF.
+    #[cfg(feature = "digest")]
+    pub fn setup_sender_with_transcript<D: digest::Digest>(
+        &self,
+        pk_r: &HPKEPublicKey,
+        transcript: D,
+        psk: Option<&[u8]>,
+        psk_id: Option<&[u8]>,
+        sk_s: Option<&HPKEPrivateKey>,
+    ) -> Result<(EncapsulatedSecret, Context<'_>), HPKEError> {
+        if <D as digest::Digest>::output_size() != self.nh {
+            return Err(HPKEError::InvalidInput);
+        }
+        let info = transcript.finalize();
+        self.setup_sender(pk_r, &info, psk, psk_id, sk_s)
+    }
+
+    /// Set up an HPKE sender like `setup_sender` with the suite negotiation
+    /// bound into the key schedule.
+    ///
+    /// This is not part of the RFC.
+    /// The `negotiation_transcript`, e.g. the offered and accepted suites, is
+    /// hashed and prepended to the `info`. A tampered negotiation yields
+    /// different keys on both sides.
+    ///
+    /// ```text
+    /// negotiation_hash = LabeledExtract("", "negotiation", negotiation_transcript)
+    /// info = concat(negotiation_hash, info)
+    /// ```
+    ///
+    /// The receiver has to use `setup_receiver_with_negotiation`.
+    pub fn setup_sender_with_negotiation(
+        &self,
+        pk_r: &HPKEPublicKey,
+        negotiation_transcript: &[u8],
+        info: &[u8],
+        psk: Option<&[u8]>,
+        psk_id: Option<&[u8]>,
+        sk_s: Option<&HPKEPrivateKey>,
+    ) -> Result<(EncapsulatedSecret, Context<'_>), HPKEError> {
+        let info = self.get_negotiation_info(negotiation_transcript, info);
+        self.setup_sender(pk_r, &info, psk, psk_id, sk_s)
+    }
+
+    /// Set up an HPKE receiver like `setup_receiver` with the suite
+    /// negotiation bound into the key schedule.
+    /// See `setup_sender_with_negotiation`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn setup_receiver_with_negotiation(
+        &self,
+        enc: &[u8],
+        sk_r: &HPKEPrivateKey,
+        negotiation_transcript: &[u8],
+        info: &[u8],
+        psk: Option<&[u8]>,
+        psk_id: Option<&[u8]>,
+        pk_s: Option<&HPKEPublicKey>,
+    ) -> Result<Context<'_>, HPKEError> {
+        let info = self.get_negotiation_info(negotiation_transcript, info);
+        self.setup_receiver(enc, sk_r, &info, psk, psk_id, pk_s)
+    }
+
+    /// Prepend the hash of the `negotiation_transcript` to `info`.
+    fn get_negotiation_info(&self, negotiation_transcript: &[u8], info: &[u8]) -> Vec<u8> {
+        let negotiation_hash = self.kdf.labeled_extract(
+            &[],
+            &self.get_ciphersuite(),
+            "negotiation",
+            negotiation_transcript,
+        );
+        util::concat(&[&negotiation_hash, info])
+    }
+
+    /// Set up an HPKE sender in the Auth or AuthPSK mode like `setup_sender`
+    /// after checking the sender's key against a certificate chain.
+    ///
+    /// The `verifier` is called with the public key of `sk_s` and the
+    /// `cert_chain` and has to return `true` if the chain certifies the key.
+    /// Returns a `CertificateRejected` error if it returns `false`, and an
+    /// `InvalidConfig` error if the mode isn't Auth or AuthPSK.
+    #[allow(clippy::too_many_arguments)]
+    pub fn setup_sender_auth_with_cert<F>(
+        &self,
+        pk_r: &HPKEPublicKey,
+        info: &[u8],
+        psk: Option<&[u8]>,
+        psk_id: Option<&[u8]>,
+        sk_s: &HPKEPrivateKey,
+        cert_chain: &[&[u8]],
+        verifier: F,
+    ) -> Result<(EncapsulatedSecret, Context<'_>), HPKEError>
+    where
+        F: FnOnce(&HPKEPublicKey, &[&[u8]]) -> bool,
+    {
+        if self.mode != Mode::Auth && self.mode != Mode::AuthPsk {
+            return Err(HPKEError::InvalidConfig);
+        }
+        let pk_s = HPKEPublicKey::new(self.kem.sk_to_pk(&sk_s.value)?);
+        if !verifier(&pk_s, cert_chain) {
+            return Err(HPKEError::CertificateRejected);
+        }
+        self.setup_sender(pk_r, info, psk, psk_id, Some(sk_s))
+    }
+
+    /// Set up an HPKE sender like `setup_sender` that binds `info` to every
+    /// message.
+    ///
+    /// This is not part of the RFC.
+    /// The returned context prepends `info_hash` to the aad of every `seal` and
+    /// `open` call, after the prefix set with `with_aad_prefix`.
+    /// The receiver has to use `setup_receiver_binding_info`.
+    pub fn setup_sender_binding_info(
+        &self,
+        pk_r: &HPKEPublicKey,
+        info: &[u8],
+        psk: Option<&[u8]>,
+        psk_id: Option<&[u8]>,
+        sk_s: Option<&HPKEPrivateKey>,
+    ) -> Result<(EncapsulatedSecret, Context<'_>), HPKEError> {
+        let (enc, mut context) = self.setup_sender(pk_r, info, psk, psk_id, sk_s)?;
+        context
+            .aad_prefix
+            .extend_from_slice(&self.get_info_hash(info, &self.get_ciphersuite()));
+        Ok((enc, context))
+    }
+
+    /// Set up an HPKE receiver like `setup_receiver` that binds `info` to every
+    /// message.
+    ///
+    /// This is not part of the RFC.
+    /// The returned context prepends `info_hash` to the aad of every `seal` and
+    /// `open` call. The sender has to use `setup_sender_binding_info`.
+    pub fn setup_receiver_binding_info(
+        &self,
+        enc: &[u8],
+        sk_r: &HPKEPrivateKey,
+        info: &[u8],
+        psk: Option<&[u8]>,
+        psk_id: Option<&[u8]>,
+        pk_s: Option<&HPKEPublicKey>,
+    ) -> Result<Context<'_>, HPKEError> {
+        let mut context = self.setup_receiver(enc, sk_r, info, psk, psk_id, pk_s)?;
+        context
+            .aad_prefix
+            .extend_from_slice(&self.get_info_hash(info, &self.get_ciphersuite()));
+        Ok(context)
+    }
+
+    /// 6. Single-Shot APIs
+    /// 6.1. Encryption and Decryption
+    ///
+    /// Single shot API to encrypt the bytes in `plain_text` to the public key
+    /// `pk_r`.
+    ///
+    /// Returns the encapsulated secret and the ciphertext, or an error.
+    #[allow(clippy::too_many_arguments)]
+    pub fn seal(
+        &self,
+        pk_r: &HPKEPublicKey,
+        info: &[u8],
+        aad: &[u8],
+        plain_txt: &[u8],
+        psk: Option<&[u8]>,
+        psk_id: Option<&[u8]>,
+        sk_s: Option<&HPKEPrivateKey>,
+    ) -> Result<(EncapsulatedSecret, Ciphertext), HPKEError> {
+        let (enc, mut context) = self.setup_sender(pk_r, info, psk, psk_id, sk_s)?;
+        let ctxt = context.seal(aad, plain_txt)?;
+        Ok((enc, ctxt))
+    }
+
+    /// Set up an HPKE sender and seal the first message like `seal`, but keep
+    /// the context.
+    ///
+    /// Returns the encapsulated secret, the ciphertext, and the context to
+    /// seal further messages with, or an error.
+    #[allow(clippy::too_many_arguments)]
+    pub fn setup_and_seal(
+        &self,
+        pk_r: &HPKEPublicKey,
+        info: &[u8],
+        aad: &[u8],
+        plain_txt: &[u8],
+        psk: Option<&[u8]>,
+        psk_id: Option<&[u8]>,
+        sk_s: Option<&HPKEPrivateKey>,
+    ) -> Result<(EncapsulatedSecret, Ciphertext, Context<'_>), HPKEError> {
+        let (enc, mut context) = self.setup_sender(pk_r, info, psk, psk_id, sk_s)?;
+        let ctxt = context.seal(aad, plain_txt)?;
+        Ok((enc, ctxt, context))
+    }
+
+    /// 6. Single-Shot APIs
+    /// 6.1. Encryption and Decryption
+    ///
+    /// Single shot API to decrypt the bytes in `ct` with the private key `sk_r`.
+    ///
+    /// Returns the decrypted plain text, or an error.
+    /// All inputs may be attacker controlled. Malformed `enc` or `ct` values
+    /// result in an error and never in a panic.
+    #[allow(clippy::too_many_arguments)]
+    pub fn open(
+        &self,
+        enc: &[u8],
+        sk_r: &HPKEPrivateKey,
+        info: &[u8],
+        aad: &[u8],
+        ct: &[u8],
+        psk: Option<&[u8]>,
+        psk_id: Option<&[u8]>,
+        pk_s: Option<&HPKEPublicKey>,
+    ) -> Result<Plaintext, HPKEError> {
+        if ct.len() > self.limits.max_open_input {
+            return Err(HPKEError::InvalidInput);
+        }
+        let mut context = self.setup_receiver(enc, sk_r, info, psk, psk_id, pk_s)?;
+        context.open(aad, ct)
+    }
+
+    /// Single shot API like `seal` that binds the ciphertext to the
+    /// encapsulated secret.
+    ///
+    /// This is not part of the RFC.
+    /// The encapsulated secret is prepended to the `aad`, so the ciphertext
+    /// fails to open with any other `enc`. Because `enc` has the fixed length
+    /// `Nenc` the concatenation is unambiguous.
+    ///
+    /// ```text
+    /// ct = context.Seal(concat(enc, aad), pt)
+    /// ```
+    ///
+    /// The receiver has to use `open_binding_enc`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn seal_binding_enc(
+        &self,
+        pk_r: &HPKEPublicKey,
+        info: &[u8],
+        aad: &[u8],
+        plain_txt: &[u8],
+        psk: Option<&[u8]>,
+        psk_id: Option<&[u8]>,
+        sk_s: Option<&HPKEPrivateKey>,
+    ) -> Result<(EncapsulatedSecret, Ciphertext), HPKEError> {
+        let (enc, mut context) = self.setup_sender(pk_r, info, psk, psk_id, sk_s)?;
+        let ctxt = context.seal_incremental_aad(&[&enc, aad], plain_txt)?;
+        Ok((enc, ctxt))
+    }
+
+    /// Single shot API to decrypt the bytes in `ct` from `seal_binding_enc`.
+    ///
+    /// Returns the decrypted plain text, or an error if `ct` doesn't belong to
+    /// `enc`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn open_binding_enc(
+        &self,
+        enc: &[u8],
+        sk_r: &HPKEPrivateKey,
+        info: &[u8],
+        aad: &[u8],
+        ct: &[u8],
+        psk: Option<&[u8]>,
+        psk_id: Option<&[u8]>,
+        pk_s: Option<&HPKEPublicKey>,
+    ) -> Result<Plaintext, HPKEError> {
+        let mut context = self.setup_receiver(enc, sk_r, info, psk, psk_id, pk_s)?;
+        context.open_vectored(&[enc, aad], ct)
+    }
+
+    /// Wrap the data encryption key `dek` to the public key `pk_r`.
+    ///
+    /// This is `seal` with `dek` as plain text and an empty aad. Every call
+    /// uses a fresh context.
+    /// Use `unwrap_key` to get the key back.
+    pub fn wrap_key(
+        &self,
+        pk_r: &HPKEPublicKey,
+        info: &[u8],
+        dek: &[u8],
+        psk: Option<&[u8]>,
+        psk_id: Option<&[u8]>,
+        sk_s: Option<&HPKEPrivateKey>,
+    ) -> Result<(EncapsulatedSecret, Ciphertext), HPKEError> {
+        self.seal(pk_r, info, &[], dek, psk, psk_id, sk_s)
+    }
+
+    /// Unwrap a data encryption key that was wrapped with `wrap_key`.
+    ///
+    /// The key is returned in a `Zeroizing` buffer.
+    #[allow(clippy::too_many_arguments)]
+    pub fn unwrap_key(
+        &self,
+        enc: &[u8],
+        sk_r: &HPKEPrivateKey,
+        info: &[u8],
+        wrapped: &[u8],
+        psk: Option<&[u8]>,
+        psk_id: Option<&[u8]>,
+        pk_s: Option<&HPKEPublicKey>,
+    ) -> Result<Zeroizing<Vec<u8>>, HPKEError> {
+        Ok(Zeroizing::new(self.open(
+            enc,
+            sk_r,
+            info,
+            &[],
+            wrapped,
+            psk,
+            psk_id,
+            pk_s,
+        )?))
+    }
+
+    /// Open `ct` with `sk_r` and seal the plain text again to `pk_r_new`, e.g.
+    /// on a relay.
+    ///
+    /// This is not proxy re-encryption; the plain text is decrypted in the
+    /// process and zeroized afterwards.
+    /// The same `info`, `aad`, and PSK are used for both operations.
+    /// Returns an `InvalidConfig` error in the Auth and AuthPSK modes because
+    /// the relay can't authenticate as the original sender.
+    ///
+    /// Returns the new encapsulated secret and ciphertext.
+    #[allow(clippy::too_many_arguments)]
+    pub fn rewrap(
+        &self,
+        enc: &[u8],
+        sk_r: &HPKEPrivateKey,
+        pk_r_new: &HPKEPublicKey,
+        info: &[u8],
+        aad: &[u8],
+        ct: &[u8],
+        psk: Option<&[u8]>,
+        psk_id: Option<&[u8]>,
+    ) -> Result<(EncapsulatedSecret, Ciphertext), HPKEError> {
+        if self.mode == Mode::Auth || self.mode == Mode::AuthPsk {
+            return Err(HPKEError::InvalidConfig);
         }
+        let mut ptxt = self.open(enc, sk_r, info, aad, ct, psk, psk_id, None)?;
+        self.seal_and_zeroize(pk_r_new, info, aad, &mut ptxt, psk, psk_id)
     }
 
-    /// Set up an HPKE sender.
-    ///
-    /// For the base and PSK modes this encapsulates the public key `pk_r`
-    /// of the receiver.
-    /// For the Auth and AuthPSK modes this encapsulates and authenticates
-    /// the public key `pk_r` of the receiver with the senders secret key `sk_s`.
+    /// Seal `plain_txt` like `seal` in a mode without sender key and zeroize
+    /// it afterwards.
     ///
-    /// The encapsulated secret is returned together with the context.
-    /// If the secret key is missing in an authenticated mode, an error is returned.
-    pub fn setup_sender(
+    /// The plain text is zeroized even if sealing fails.
+    fn seal_and_zeroize(
         &self,
         pk_r: &HPKEPublicKey,
         info: &[u8],
+        aad: &[u8],
+        plain_txt: &mut Vec<u8>,
         psk: Option<&[u8]>,
         psk_id: Option<&[u8]>,
-        sk_s: Option<&HPKEPrivateKey>,
-    ) -> Result<(EncapsulatedSecret, Context), HPKEError> {
-        let (zz, enc) = match self.mode {
-            Mode::Base | Mode::Psk => self.kem.encaps(&pk_r.value),
-            Mode::Auth | Mode::AuthPsk => {
-                let sk_s = match sk_s {
-                    Some(s) => &s.value,
-                    None => return Err(HPKEError::InvalidInput),
-                };
-                self.kem.auth_encaps(&pk_r.value, sk_s)
-            }
-        };
-        Ok((
-            enc,
-            self.key_schedule(
-                &zz,
-                info,
-                psk.unwrap_or_default(),
-                psk_id.unwrap_or_default(),
-            )?,
-        ))
+    ) -> Result<(EncapsulatedSecret, Ciphertext), HPKEError> {
+        let result = self.seal(pk_r, info, aad, plain_txt, psk, psk_id, None);
+        plain_txt.zeroize();
+        result
     }
 
-    /// Set up an HPKE receiver.
+    /// Open early (0-RTT) data `ct` like `open`.
     ///
-    /// For the base and PSK modes this decapsulates `enc` with the secret key
-    /// `sk_r` of the receiver.
-    /// For the Auth and AuthPSK modes this decapsulates and authenticates `enc`
-    /// with the secret key `sk_r` of the receiver and the senders public key `pk_s`.
-    ///
-    /// The context based on the decapsulated values and, if present, the PSK is
-    /// returned.
-    /// If the secret key is missing in an authenticated mode, an error is returned.
-    pub fn setup_receiver(
+    /// Early data is sealed before the receiver took part in the session, so
+    /// an attacker can replay it. The returned `ReplayRisk` reminds the caller
+    /// to apply its own anti-replay mechanism, e.g. a cache of seen `enc`
+    /// values, before acting on the plain text.
+    #[allow(clippy::too_many_arguments)]
+    pub fn open_early_data(
         &self,
         enc: &[u8],
         sk_r: &HPKEPrivateKey,
         info: &[u8],
         psk: Option<&[u8]>,
         psk_id: Option<&[u8]>,
+        aad: &[u8],
+        ct: &[u8],
         pk_s: Option<&HPKEPublicKey>,
-    ) -> Result<Context, HPKEError> {
-        let zz = match self.mode {
-            Mode::Base | Mode::Psk => self.kem.decaps(enc, &sk_r.value),
-            Mode::Auth | Mode::AuthPsk => {
-                let pk_s = match pk_s {
-                    Some(s) => &s.value,
-                    None => return Err(HPKEError::InvalidInput),
-                };
-                self.kem.auth_decaps(enc, &sk_r.value, pk_s)
-            }
-        };
-        self.key_schedule(
-            &zz,
-            info,
-            psk.unwrap_or_default(),
-            psk_id.unwrap_or_default(),
-        )
+    ) -> Result<(Plaintext, ReplayRisk), HPKEError> {
+        let ptxt = self.open(enc, sk_r, info, aad, ct, psk, psk_id, pk_s)?;
+        Ok((ptxt, ReplayRisk::Unchecked))
     }
 
-    /// 6. Single-Shot APIs
-    /// 6.1. Encryption and Decryption
+    /// Set up an HPKE sender and encode the first flight of the session, i.e.
+    /// the configuration, the encapsulated secret, and the first ciphertext,
+    /// into a single handshake message.
     ///
-    /// Single shot API to encrypt the bytes in `plain_text` to the public key
-    /// `pk_r`.
+    /// The wire layout is (all integers big-endian)
     ///
-    /// Returns the encapsulated secret and the ciphertext, or an error.
+    /// ```text
+    /// struct {
+    ///     uint8 mode;
+    ///     uint16 kem_id;
+    ///     uint16 kdf_id;
+    ///     uint16 aead_id;
+    ///     opaque enc<0..2^16-1>;
+    ///     opaque ct<0..2^32-1>;
+    /// } Handshake;
+    /// ```
+    ///
+    /// where `enc` and `ct` are prefixed with their length as `uint16` and
+    /// `uint32` respectively.
+    /// Use `decode_handshake` on the receiver side.
     #[allow(clippy::too_many_arguments)]
-    pub fn seal(
+    pub fn encode_handshake(
         &self,
         pk_r: &HPKEPublicKey,
         info: &[u8],
@@ -382,32 +2863,169 @@ impl Hpke {
         psk: Option<&[u8]>,
         psk_id: Option<&[u8]>,
         sk_s: Option<&HPKEPrivateKey>,
-    ) -> Result<(EncapsulatedSecret, Ciphertext), HPKEError> {
-        let (enc, mut context) = self.setup_sender(pk_r, info, psk, psk_id, sk_s)?;
-        let ctxt = context.seal(aad, plain_txt)?;
-        Ok((enc, ctxt))
+    ) -> Result<Vec<u8>, HPKEError> {
+        let (enc, ctxt) = self.seal(pk_r, info, aad, plain_txt, psk, psk_id, sk_s)?;
+        if enc.len() > u16::MAX as usize || ctxt.len() > u32::MAX as usize {
+            return Err(HPKEError::InvalidInput);
+        }
+        Ok(util::concat(&[
+            &[self.mode as u8],
+            &(self.kem_id as u16).to_be_bytes(),
+            &(self.kdf_id as u16).to_be_bytes(),
+            &(self.aead_id as u16).to_be_bytes(),
+            &(enc.len() as u16).to_be_bytes(),
+            &enc,
+            &(ctxt.len() as u32).to_be_bytes(),
+            &ctxt,
+        ]))
     }
 
-    /// 6. Single-Shot APIs
-    /// 6.1. Encryption and Decryption
+    /// Decode a handshake message created with `encode_handshake`, set up an
+    /// HPKE receiver, and open the first ciphertext.
     ///
-    /// Single shot API to decrypt the bytes in `ct` with the private key `sk_r`.
+    /// Returns the plain text and the context for subsequent messages.
+    /// Returns an `InvalidInput` error if the message is malformed and an
+    /// `InvalidConfig` error if the mode or suite in the message doesn't match
+    /// this configuration.
+    #[allow(clippy::too_many_arguments)]
+    pub fn decode_handshake(
+        &self,
+        sk_r: &HPKEPrivateKey,
+        incoming: &[u8],
+        info: &[u8],
+        aad: &[u8],
+        psk: Option<&[u8]>,
+        psk_id: Option<&[u8]>,
+        pk_s: Option<&HPKEPublicKey>,
+    ) -> Result<(Plaintext, Context<'_>), HPKEError> {
+        let (header, rest) = util::split_at_checked(incoming, 7).ok_or(HPKEError::InvalidInput)?;
+        if header[0] != self.mode as u8
+            || header[1..3] != (self.kem_id as u16).to_be_bytes()
+            || header[3..5] != (self.kdf_id as u16).to_be_bytes()
+            || header[5..7] != (self.aead_id as u16).to_be_bytes()
+        {
+            return Err(HPKEError::InvalidConfig);
+        }
+
+        let (enc_len, rest) = util::split_at_checked(rest, 2).ok_or(HPKEError::InvalidInput)?;
+        let (enc, rest) =
+            util::split_at_checked(rest, u16::from_be_bytes([enc_len[0], enc_len[1]]) as usize)
+                .ok_or(HPKEError::InvalidInput)?;
+        let (ctxt_len, rest) = util::split_at_checked(rest, 4).ok_or(HPKEError::InvalidInput)?;
+        let ctxt_len = u32::from_be_bytes([ctxt_len[0], ctxt_len[1], ctxt_len[2], ctxt_len[3]]);
+        let (ctxt, rest) =
+            util::split_at_checked(rest, ctxt_len as usize).ok_or(HPKEError::InvalidInput)?;
+        if !rest.is_empty() {
+            return Err(HPKEError::InvalidInput);
+        }
+
+        let mut context = self.setup_receiver(enc, sk_r, info, psk, psk_id, pk_s)?;
+        let ptxt = context.open(aad, ctxt)?;
+        Ok((ptxt, context))
+    }
+
+    /// Single shot API like `seal` that returns the encapsulated secret and
+    /// the ciphertext as unpadded base64url strings.
+    #[cfg(feature = "base64")]
+    #[allow(clippy::too_many_arguments)]
+    pub fn seal_base64(
+        &self,
+        pk_r: &HPKEPublicKey,
+        info: &[u8],
+        aad: &[u8],
+        plain_txt: &[u8],
+        psk: Option<&[u8]>,
+        psk_id: Option<&[u8]>,
+        sk_s: Option<&HPKEPrivateKey>,
+    ) -> Result<(String, String), HPKEError> {
+        use base64::Engine;
+
+        let (enc, ctxt) = self.seal(pk_r, info, aad, plain_txt, psk, psk_id, sk_s)?;
+        let engine = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+        Ok((engine.encode(enc), engine.encode(ctxt)))
+    }
+
+    /// Single shot API like `open` that takes the encapsulated secret and the
+    /// ciphertext as unpadded base64url strings, as returned by `seal_base64`.
     ///
-    /// Returns the decrypted plain text, or an error.
+    /// Returns an `InvalidInput` error if `enc` or `ct` are not valid unpadded
+    /// base64url.
+    #[cfg(feature = "base64")]
     #[allow(clippy::too_many_arguments)]
-    pub fn open(
+    pub fn open_base64(
         &self,
-        enc: &[u8],
+        enc: &str,
         sk_r: &HPKEPrivateKey,
         info: &[u8],
         aad: &[u8],
-        ct: &[u8],
+        ct: &str,
         psk: Option<&[u8]>,
         psk_id: Option<&[u8]>,
         pk_s: Option<&HPKEPublicKey>,
     ) -> Result<Plaintext, HPKEError> {
-        let mut context = self.setup_receiver(enc, sk_r, info, psk, psk_id, pk_s)?;
-        context.open(aad, ct)
+        use base64::Engine;
+
+        let engine = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+        let enc = engine.decode(enc).map_err(|_| HPKEError::InvalidInput)?;
+        let ct = engine.decode(ct).map_err(|_| HPKEError::InvalidInput)?;
+        self.open(&enc, sk_r, info, aad, &ct, psk, psk_id, pk_s)
+    }
+
+    /// Single shot API to decrypt the bytes in `ct` with one of the private keys
+    /// in `candidate_sks`, like `open`.
+    ///
+    /// All candidate keys are tried, even after one succeeded, and the
+    /// matching index and plain text are selected in constant time. So the
+    /// work doesn't depend on which key matched, apart from differences
+    /// within the backends between a failing and a succeeding open.
+    ///
+    /// Returns the index of the key that decrypted `ct` and the decrypted
+    /// plain text. If no key works the error is an `OpenError`, unless every
+    /// candidate failed with the same error, e.g. an `InvalidInput` for a too
+    /// long `ct`, which is returned instead.
+    #[allow(clippy::too_many_arguments)]
+    pub fn open_trial(
+        &self,
+        enc: &[u8],
+        candidate_sks: &[&HPKEPrivateKey],
+        info: &[u8],
+        aad: &[u8],
+        ct: &[u8],
+        psk: Option<&[u8]>,
+        psk_id: Option<&[u8]>,
+        pk_s: Option<&HPKEPublicKey>,
+    ) -> Result<(usize, Plaintext), HPKEError> {
+        use subtle::{Choice, ConditionallySelectable};
+
+        let pt_len = ct.len().saturating_sub(self.aead.get_nt());
+        let mut found = Choice::from(0);
+        let mut index = 0u64;
+        let mut ptxt = Zeroizing::new(vec![0u8; pt_len]);
+        let mut common_err = None;
+        for (i, sk_r) in candidate_sks.iter().enumerate() {
+            let (opened, candidate) = match self.open(enc, sk_r, info, aad, ct, psk, psk_id, pk_s) {
+                Ok(candidate) => (Choice::from(1), Zeroizing::new(candidate)),
+                Err(e) => {
+                    common_err = match (i, common_err) {
+                        (0, _) => Some(e),
+                        (_, Some(c)) if c == e => Some(c),
+                        _ => Some(HPKEError::OpenError),
+                    };
+                    (Choice::from(0), Zeroizing::new(vec![0u8; pt_len]))
+                }
+            };
+            let select = opened & !found;
+            index.conditional_assign(&(i as u64), select);
+            for (p, c) in ptxt.iter_mut().zip(candidate.iter()) {
+                p.conditional_assign(c, select);
+            }
+            found |= opened;
+        }
+        if bool::from(found) {
+            Ok((index as usize, ptxt.to_vec()))
+        } else {
+            Err(common_err.unwrap_or(HPKEError::OpenError))
+        }
     }
 
     /// 6. Single-Shot APIs
@@ -456,7 +3074,52 @@ impl Hpke {
         Ok(context.export(exporter_context, length))
     }
 
+    /// Same as `send_export` but the exporter secret is zeroized on drop.
+    #[allow(clippy::too_many_arguments)]
+    pub fn send_export_zeroizing(
+        &self,
+        pk_r: &HPKEPublicKey,
+        info: &[u8],
+        psk: Option<&[u8]>,
+        psk_id: Option<&[u8]>,
+        sk_s: Option<&HPKEPrivateKey>,
+        exporter_context: &[u8],
+        length: usize,
+    ) -> Result<(EncapsulatedSecret, Zeroizing<Vec<u8>>), HPKEError> {
+        let (enc, context) = self.setup_sender(pk_r, info, psk, psk_id, sk_s)?;
+        Ok((enc, context.export_zeroizing(exporter_context, length)))
+    }
+
+    /// Same as `receiver_export` but the exporter secret is zeroized on drop.
+    #[allow(clippy::too_many_arguments)]
+    pub fn receiver_export_zeroizing(
+        &self,
+        enc: &[u8],
+        sk_r: &HPKEPrivateKey,
+        info: &[u8],
+        psk: Option<&[u8]>,
+        psk_id: Option<&[u8]>,
+        pk_s: Option<&HPKEPublicKey>,
+        exporter_context: &[u8],
+        length: usize,
+    ) -> Result<Zeroizing<Vec<u8>>, HPKEError> {
+        let context = self.setup_receiver(enc, sk_r, info, psk, psk_id, pk_s)?;
+        Ok(context.export_zeroizing(exporter_context, length))
+    }
+
+    /// Check that this configuration is in the HPKE `mode`.
+    fn check_mode(&self, mode: Mode) -> Result<(), HPKEError> {
+        if self.mode != mode {
+            return Err(HPKEError::InvalidConfig);
+        }
+        Ok(())
+    }
+
     /// Verify PSKs.
+    ///
+    /// An empty `psk` or `psk_id` is the RFC's default, i.e. no PSK.
+    /// The callers map `None` and `Some(&[])` to the empty default, so a
+    /// `Some(&[])` PSK with a non-empty `psk_id` is inconsistent.
     #[inline(always)]
     fn verify_psk_inputs(&self, psk: &[u8], psk_id: &[u8]) -> Result<(), HPKEError> {
         let got_psk = !psk.is_empty();
@@ -465,15 +3128,16 @@ impl Hpke {
             return Err(HPKEError::InconsistentPsk);
         }
 
-        if got_psk && (self.mode == Mode::Base || self.mode == Mode::Auth) {
-            return Err(HPKEError::UnnecessaryPsk);
-        }
-        if !got_psk && (self.mode == Mode::Psk || self.mode == Mode::AuthPsk) {
-            return Err(HPKEError::MissingPsk);
+        let expected_psk = self.mode == Mode::Psk || self.mode == Mode::AuthPsk;
+        if got_psk != expected_psk {
+            return Err(HPKEError::PskMismatch {
+                expected_psk,
+                got_psk,
+            });
         }
 
         // The PSK MUST have at least 32 bytes of entropy and SHOULD be of length Nh bytes or longer.
-        if (self.mode == Mode::Psk || self.mode == Mode::AuthPsk) && psk.len() < 32 {
+        if expected_psk && psk.len() < 32 {
             return Err(HPKEError::InsecurePsk);
         }
 
@@ -490,15 +3154,72 @@ impl Hpke {
         ])
     }
 
+    /// `secret = LabeledExtract(shared_secret, "secret", psk)`
+    #[inline]
+    fn get_secret(&self, shared_secret: &[u8], psk: &[u8], suite_id: &[u8]) -> Vec<u8> {
+        self.kdf
+            .labeled_extract(shared_secret, suite_id, "secret", psk)
+    }
+
     #[inline]
     fn get_key_schedule_context(&self, info: &[u8], psk_id: &[u8], suite_id: &[u8]) -> Vec<u8> {
         let psk_id_hash = self
             .kdf
             .labeled_extract(&[0], suite_id, "psk_id_hash", psk_id);
-        let info_hash = self.kdf.labeled_extract(&[0], suite_id, "info_hash", info);
+        let info_hash = self.get_info_hash(info, suite_id);
         util::concat(&[&[self.mode as u8], &psk_id_hash, &info_hash])
     }
 
+    #[inline]
+    fn get_info_hash(&self, info: &[u8], suite_id: &[u8]) -> Vec<u8> {
+        self.kdf.labeled_extract(&[0], suite_id, "info_hash", info)
+    }
+
+    /// Check the key schedule inputs and compute the `secret` and the
+    /// `key_schedule_context`.
+    fn key_schedule_secret(
+        &self,
+        shared_secret: &[u8],
+        info: &[u8],
+        psk: &[u8],
+        psk_id: &[u8],
+        suite_id: &[u8],
+    ) -> Result<(Vec<u8>, Vec<u8>), HPKEError> {
+        if info.len() > self.limits.max_info || psk.len() > self.limits.max_psk {
+            return Err(HPKEError::InvalidInput);
+        }
+        if !self.assume_valid_psk {
+            self.verify_psk_inputs(psk, psk_id)?;
+        }
+        let key_schedule_context = self.get_key_schedule_context(info, psk_id, suite_id);
+        let secret = self.get_secret(shared_secret, psk, suite_id);
+        Ok((secret, key_schedule_context))
+    }
+
+    /// Expand the key schedule `secret` into the key, base nonce, and exporter
+    /// secret.
+    fn key_schedule_expand(
+        &self,
+        secret: &[u8],
+        key_schedule_context: &[u8],
+        suite_id: &[u8],
+    ) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        let mut outputs = self
+            .kdf
+            .labeled_expand_multi(
+                secret,
+                suite_id,
+                key_schedule_context,
+                &[("key", self.nk), ("base_nonce", self.nn), ("exp", self.nh)],
+            )
+            .into_iter();
+        // Unwrapping here is fine because there's one output per label.
+        let key = outputs.next().unwrap();
+        let base_nonce = outputs.next().unwrap();
+        let exporter_secret = outputs.next().unwrap();
+        (key, base_nonce, exporter_secret)
+    }
+
     /// 5.1. Creating the Encryption Context
     /// Generate the HPKE context from the given input.
     ///
@@ -539,33 +3260,127 @@ impl Hpke {
         psk: &[u8],
         psk_id: &[u8],
     ) -> Result<Context, HPKEError> {
-        self.verify_psk_inputs(psk, psk_id)?;
         let suite_id = self.get_ciphersuite();
-        let key_schedule_context = self.get_key_schedule_context(info, psk_id, &suite_id);
-        let secret = self
-            .kdf
-            .labeled_extract(shared_secret, &suite_id, "secret", psk);
+        let (secret, key_schedule_context) =
+            self.key_schedule_secret(shared_secret, info, psk, psk_id, &suite_id)?;
+        let secret = Zeroizing::new(secret);
+        let (key, base_nonce, exporter_secret) =
+            self.key_schedule_expand(&secret, &key_schedule_context, &suite_id);
+
+        Ok(Context {
+            key,
+            nonce: base_nonce,
+            exporter_secret,
+            sequence_number: 0,
+            external_counter: false,
+            refresh_epoch: 0,
+            aad_prefix: self.aad_prefix.clone(),
+            hpke: self,
+        })
+    }
+
+    /// Run the key schedule like `key_schedule` but write the result into an
+    /// existing `context`.
+    ///
+    /// This allows pooling contexts.
+    /// The old key, nonce, and exporter secret are zeroized and their buffers
+    /// are reused instead of allocating a new `Context`. The key schedule
+    /// still allocates temporary buffers for its intermediate values and the
+    /// KDF outputs, which are zeroized where they hold secrets.
+    /// The sequence number is reset to 0, the external counter is disabled,
+    /// and the aad prefix is replaced with this `Hpke`'s prefix.
+    /// `context` is bound to this `Hpke` afterwards.
+    /// On error `context` is left unchanged.
+    pub fn key_schedule_into<'a>(
+        &'a self,
+        context: &mut Context<'a>,
+        shared_secret: &[u8],
+        info: &[u8],
+        psk: &[u8],
+        psk_id: &[u8],
+    ) -> Result<(), HPKEError> {
+        let suite_id = self.get_ciphersuite();
+        let (secret, key_schedule_context) =
+            self.key_schedule_secret(shared_secret, info, psk, psk_id, &suite_id)?;
+        let secret = Zeroizing::new(secret);
+        let mut labeled_info = Vec::new();
 
-        let key =
-            self.kdf
-                .labeled_expand(&secret, &suite_id, "key", &key_schedule_context, self.nk);
-        let base_nonce = self.kdf.labeled_expand(
+        self.kdf.labeled_expand_into(
+            &secret,
+            &suite_id,
+            "key",
+            &key_schedule_context,
+            self.nk,
+            &mut labeled_info,
+            &mut context.key,
+        );
+        self.kdf.labeled_expand_into(
             &secret,
             &suite_id,
             "base_nonce",
             &key_schedule_context,
             self.nn,
+            &mut labeled_info,
+            &mut context.nonce,
+        );
+        self.kdf.labeled_expand_into(
+            &secret,
+            &suite_id,
+            "exp",
+            &key_schedule_context,
+            self.nh,
+            &mut labeled_info,
+            &mut context.exporter_secret,
         );
-        let exporter_secret =
-            self.kdf
-                .labeled_expand(&secret, &suite_id, "exp", &key_schedule_context, self.nh);
+        context.sequence_number = 0;
+        context.external_counter = false;
+        context.refresh_epoch = 0;
+        context.aad_prefix.clear();
+        context.aad_prefix.extend_from_slice(&self.aad_prefix);
+        context.hpke = self;
+        Ok(())
+    }
 
-        Ok(Context {
+    /// Compute the key schedule's `secret` from the `shared_secret` and the
+    /// `psk`.
+    ///
+    /// This is only meant for debugging interop issues.
+    #[cfg(feature = "debug-trace")]
+    #[doc(hidden)]
+    pub fn compute_secret(&self, shared_secret: &[u8], psk: &[u8]) -> Vec<u8> {
+        self.get_secret(shared_secret, psk, &self.get_ciphersuite())
+    }
+
+    /// Run the key schedule like `key_schedule` and return all intermediate
+    /// values.
+    ///
+    /// This is only meant for debugging interop issues.
+    #[cfg(feature = "debug-trace")]
+    #[doc(hidden)]
+    pub fn key_schedule_debug(
+        &self,
+        shared_secret: &[u8],
+        info: &[u8],
+        psk: &[u8],
+        psk_id: &[u8],
+    ) -> Result<KeyScheduleTrace, HPKEError> {
+        let suite_id = self.get_ciphersuite();
+        let (secret, key_schedule_context) =
+            self.key_schedule_secret(shared_secret, info, psk, psk_id, &suite_id)?;
+        let (key, base_nonce, exporter_secret) =
+            self.key_schedule_expand(&secret, &key_schedule_context, &suite_id);
+        // key_schedule_context = concat(mode, psk_id_hash, info_hash)
+        let (psk_id_hash, info_hash) = key_schedule_context[1..].split_at(self.nh);
+        let (psk_id_hash, info_hash) = (psk_id_hash.to_vec(), info_hash.to_vec());
+
+        Ok(KeyScheduleTrace {
+            psk_id_hash,
+            info_hash,
+            key_schedule_context,
+            secret,
             key,
-            nonce: base_nonce,
+            base_nonce,
             exporter_secret,
-            sequence_number: 0,
-            hpke: self,
         })
     }
 
@@ -575,8 +3390,11 @@ impl Hpke {
     ///
     /// Returns an `HPKEKeyPair`.
     pub fn generate_key_pair(&self) -> HPKEKeyPair {
-        let (sk, pk) = self.kem.key_gen();
-        HPKEKeyPair::new(sk, pk)
+        if self.rng.is_some() {
+            let ikm = Zeroizing::new(self.random_vec(self.nsecret()));
+            return self.derive_key_pair(&ikm);
+        }
+        self.kem.generate_key_pair()
     }
 
     /// 7.1.2. DeriveKeyPair
@@ -587,6 +3405,50 @@ impl Hpke {
         let (pk, sk) = self.kem.derive_key_pair(ikm);
         HPKEKeyPair::new(sk, pk)
     }
+
+    /// Derive a key pair from the master input key material `master_ikm` and
+    /// a derivation `path`.
+    ///
+    /// This is not part of the RFC.
+    /// Each path element is mixed into the key material before the result is
+    /// passed to `derive_key_pair`. The same master key material and path
+    /// always result in the same key pair.
+    /// The labels use the suite id of this HPKE configuration.
+    ///
+    /// ```text
+    /// ikm = LabeledExtract("", "path_prk", master_ikm)
+    /// for index in path:
+    ///   ikm = LabeledExpand(ikm, "path", I2OSP(index, 4), Nh)
+    /// return DeriveKeyPair(ikm)
+    /// ```
+    ///
+    /// Returns an `InvalidInput` error if `master_ikm` is shorter than the
+    /// KEM's secret length.
+    pub fn derive_key_pair_path(
+        &self,
+        master_ikm: &[u8],
+        path: &[u32],
+    ) -> Result<HPKEKeyPair, HPKEError> {
+        if master_ikm.len() < self.nsecret() {
+            return Err(HPKEError::InvalidInput);
+        }
+        let suite_id = self.get_ciphersuite();
+        let mut ikm =
+            Zeroizing::new(
+                self.kdf
+                    .labeled_extract(&[], &suite_id, "path_prk", master_ikm),
+            );
+        for index in path {
+            ikm = Zeroizing::new(self.kdf.labeled_expand(
+                &ikm,
+                &suite_id,
+                "path",
+                &index.to_be_bytes(),
+                self.nh,
+            ));
+        }
+        Ok(self.derive_key_pair(&ikm))
+    }
 }
 
 impl HPKEKeyPair {
@@ -684,9 +3546,63 @@ impl HPKEPublicKey {
     }
 }
 
+impl Psk {
+    /// Create a new PSK with the identifier `id` and the `secret`.
+    ///
+    /// Returns an `InconsistentPsk` error if `id` or `secret` is empty, and an
+    /// `InsecurePsk` error if `secret` is shorter than 32 bytes.
+    pub fn new(id: Vec<u8>, secret: Vec<u8>) -> Result<Self, HPKEError> {
+        if id.is_empty() || secret.is_empty() {
+            return Err(HPKEError::InconsistentPsk);
+        }
+        if secret.len() < 32 {
+            return Err(HPKEError::InsecurePsk);
+        }
+        Ok(Self { id, secret })
+    }
+
+    /// Get the PSK identifier.
+    pub fn id(&self) -> &[u8] {
+        &self.id
+    }
+}
+
+impl std::fmt::Debug for Psk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Psk")
+            .field("id", &self.id)
+            .field("secret", &"***")
+            .finish()
+    }
+}
+
+impl Drop for Psk {
+    fn drop(&mut self) {
+        self.secret.zeroize();
+    }
+}
+
+/// Get `len` random bytes.
+/// This uses the same CSPRNG as the KEM key generation.
+pub fn random_bytes(len: usize) -> Vec<u8> {
+    evercrypt::prelude::get_random_vec(len)
+}
+
+/// Generate a key pair for the KEM `kem_mode` without an `Hpke`
+/// configuration, e.g. to provision receiver keys.
+///
+/// The key pair can be used with any `Hpke` configuration for `kem_mode`.
+/// Returns an `UnsupportedSuite` error if `kem_mode` is not implemented.
+pub fn generate_key_pair(kem_mode: kem::Mode) -> Result<HPKEKeyPair, HPKEError> {
+    let kem = kem::Kem::try_new(kem_mode).map_err(|_| HPKEError::UnsupportedSuite)?;
+    Ok(kem.generate_key_pair())
+}
+
 /// Test util module. Should be moved really.
+///
+/// This is only built for tests and with the `testing` feature.
+#[cfg(any(test, feature = "testing"))]
 pub mod test_util {
-    // TODO: don't build for release
     impl<'a> super::Context<'_> {
         /// Get a reference to the key in the context.
         #[doc(hidden)]
@@ -710,6 +3626,29 @@ pub mod test_util {
         }
     }
 
+    impl super::Hpke {
+        /// Derive a deterministic key pair for tests from a human-readable
+        /// `label`.
+        ///
+        /// **The key pair is public knowledge. Only use this in tests.**
+        ///
+        /// ```text
+        /// prk = LabeledExtract("", "test_key_pair", label)
+        /// ikm = LabeledExpand(prk, "test_key_pair", "", Nsk)
+        /// return DeriveKeyPair(ikm)
+        /// ```
+        pub fn test_key_pair(&self, label: &str) -> super::HPKEKeyPair {
+            let suite_id = self.get_ciphersuite();
+            let prk = self
+                .kdf
+                .labeled_extract(&[], &suite_id, "test_key_pair", label.as_bytes());
+            let ikm =
+                self.kdf
+                    .labeled_expand(&prk, &suite_id, "test_key_pair", &[], self.nsecret());
+            self.derive_key_pair(&ikm)
+        }
+    }
+
     /// Convert `bytes` to a hex string.
     pub fn bytes_to_hex(bytes: &[u8]) -> String {
         let mut hex = String::new();
@@ -749,13 +3688,24 @@ pub mod test_util {
     }
 }
 
+impl From<kem::Error> for HPKEError {
+    fn from(e: kem::Error) -> Self {
+        match e {
+            kem::Error::UnknownMode => HPKEError::UnknownMode,
+            kem::Error::InvalidInput => HPKEError::InvalidInput,
+            kem::Error::Unsupported => HPKEError::InvalidConfig,
+        }
+    }
+}
+
 impl From<aead::Error> for HPKEError {
     fn from(e: aead::Error) -> Self {
         match e {
             aead::Error::OpenError => HPKEError::OpenError,
-            aead::Error::InvalidNonce => HPKEError::InvalidConfig,
+            aead::Error::InvalidNonce => HPKEError::InvalidNonce,
             aead::Error::InvalidConfig => HPKEError::InvalidInput,
             aead::Error::UnknownMode => HPKEError::UnknownMode,
+            aead::Error::MessageLimitReached => HPKEError::MessageLimitReached,
         }
     }
 }
@@ -0,0 +1,81 @@
+//! PEM encoded SubjectPublicKeyInfo (SPKI) import and export for HPKE public
+//! keys.
+
+use spki::der::{pem::LineEnding, Any, DecodePem, EncodePem};
+use spki::{AlgorithmIdentifierOwned, ObjectIdentifier, SubjectPublicKeyInfoOwned};
+
+use crate::{kem, HPKEError, HPKEPublicKey};
+
+/// id-ecPublicKey (RFC 5480)
+const ID_EC_PUBLIC_KEY: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.2.1");
+
+/// secp256r1 (RFC 5480)
+const SECP256R1: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.3.1.7");
+
+/// id-X25519 (RFC 8410)
+const ID_X25519: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.101.110");
+
+impl HPKEPublicKey {
+    /// Read an HPKE public key from a PEM encoded SubjectPublicKeyInfo.
+    ///
+    /// Supported are X25519 and P256 keys.
+    /// Returns an `InvalidInput` error if the PEM can't be parsed or holds a
+    /// key for an unsupported algorithm.
+    pub fn from_spki_pem(pem: &str) -> Result<HPKEPublicKey, HPKEError> {
+        let spki = SubjectPublicKeyInfoOwned::from_pem(pem).map_err(|_| HPKEError::InvalidInput)?;
+        let expected_len = match spki.algorithm.oid {
+            ID_X25519 => 32,
+            ID_EC_PUBLIC_KEY => {
+                let curve: ObjectIdentifier = spki
+                    .algorithm
+                    .parameters
+                    .as_ref()
+                    .ok_or(HPKEError::InvalidInput)?
+                    .decode_as()
+                    .map_err(|_| HPKEError::InvalidInput)?;
+                if curve != SECP256R1 {
+                    return Err(HPKEError::InvalidInput);
+                }
+                65
+            }
+            _ => return Err(HPKEError::InvalidInput),
+        };
+
+        let value = spki
+            .subject_public_key
+            .as_bytes()
+            .ok_or(HPKEError::InvalidInput)?;
+        if value.len() != expected_len {
+            return Err(HPKEError::InvalidInput);
+        }
+        Ok(HPKEPublicKey::new(value.to_vec()))
+    }
+
+    /// Encode this public key as PEM encoded SubjectPublicKeyInfo for the KEM
+    /// `kem_mode`.
+    ///
+    /// Supported are X25519 and P256 keys.
+    /// Returns an `InvalidConfig` error for other KEMs.
+    pub fn to_spki_pem(&self, kem_mode: kem::Mode) -> Result<String, HPKEError> {
+        let algorithm = match kem_mode {
+            kem::Mode::DhKem25519 => AlgorithmIdentifierOwned {
+                oid: ID_X25519,
+                parameters: None,
+            },
+            kem::Mode::DhKemP256 => AlgorithmIdentifierOwned {
+                oid: ID_EC_PUBLIC_KEY,
+                parameters: Some(
+                    Any::encode_from(&SECP256R1).map_err(|_| HPKEError::InvalidInput)?,
+                ),
+            },
+            _ => return Err(HPKEError::InvalidConfig),
+        };
+        let spki = SubjectPublicKeyInfoOwned {
+            algorithm,
+            subject_public_key: spki::der::asn1::BitString::from_bytes(&self.value)
+                .map_err(|_| HPKEError::InvalidInput)?,
+        };
+        spki.to_pem(LineEnding::LF)
+            .map_err(|_| HPKEError::InvalidInput)
+    }
+}
@@ -0,0 +1,99 @@
+//! PKCS#8 DER encoded private key import and export for HPKE key pairs.
+
+use ::pkcs8::der::asn1::{AnyRef, ObjectIdentifier, OctetStringRef};
+use ::pkcs8::der::{Decode, Encode};
+use ::pkcs8::{AlgorithmIdentifierRef, PrivateKeyInfo};
+use sec1::EcPrivateKey;
+
+use crate::{kem, HPKEError, HPKEKeyPair, Zeroizing};
+
+/// id-ecPublicKey (RFC 5480)
+const ID_EC_PUBLIC_KEY: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.2.1");
+
+/// secp256r1 (RFC 5480)
+const SECP256R1: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.3.1.7");
+
+/// id-X25519 (RFC 8410)
+const ID_X25519: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.101.110");
+
+impl HPKEKeyPair {
+    /// Read an HPKE key pair for the KEM `kem_mode` from a PKCS#8 DER encoded
+    /// private key.
+    ///
+    /// The public key is derived from the private key.
+    /// Supported are X25519 and P256 keys.
+    /// Returns an `InvalidInput` error if the DER can't be parsed or holds a
+    /// key for a different algorithm, and an `InvalidConfig` error for other
+    /// KEMs.
+    pub fn from_pkcs8_der(der: &[u8], kem_mode: kem::Mode) -> Result<HPKEKeyPair, HPKEError> {
+        let info = PrivateKeyInfo::from_der(der).map_err(|_| HPKEError::InvalidInput)?;
+        let (sk, encoded_pk) = match kem_mode {
+            kem::Mode::DhKem25519 => {
+                if info.algorithm.oid != ID_X25519 || info.algorithm.parameters.is_some() {
+                    return Err(HPKEError::InvalidInput);
+                }
+                let sk = OctetStringRef::from_der(info.private_key)
+                    .map_err(|_| HPKEError::InvalidInput)?;
+                (sk.as_bytes(), None)
+            }
+            kem::Mode::DhKemP256 => {
+                let curve = info
+                    .algorithm
+                    .parameters_oid()
+                    .map_err(|_| HPKEError::InvalidInput)?;
+                if info.algorithm.oid != ID_EC_PUBLIC_KEY || curve != SECP256R1 {
+                    return Err(HPKEError::InvalidInput);
+                }
+                let ec_key = EcPrivateKey::from_der(info.private_key)
+                    .map_err(|_| HPKEError::InvalidInput)?;
+                (ec_key.private_key, ec_key.public_key)
+            }
+            _ => return Err(HPKEError::InvalidConfig),
+        };
+
+        // This validates the private key.
+        let pk = kem::Kem::new(kem_mode).sk_to_pk(sk)?;
+        if let Some(encoded_pk) = encoded_pk {
+            if encoded_pk != pk.as_slice() {
+                return Err(HPKEError::InvalidInput);
+            }
+        }
+        Ok(HPKEKeyPair::new(sk.to_vec(), pk))
+    }
+
+    /// Encode the private key of this key pair as PKCS#8 DER for the KEM
+    /// `kem_mode`.
+    ///
+    /// Supported are X25519 and P256 keys.
+    /// Returns an `InvalidConfig` error for other KEMs.
+    pub fn to_pkcs8_der(&self, kem_mode: kem::Mode) -> Result<Zeroizing<Vec<u8>>, HPKEError> {
+        let sk = &self.private_key.value;
+        let (oid, parameters, private_key) = match kem_mode {
+            kem::Mode::DhKem25519 => {
+                let private_key = OctetStringRef::new(sk)
+                    .and_then(|sk| sk.to_der())
+                    .map_err(|_| HPKEError::InvalidInput)?;
+                (ID_X25519, None, Zeroizing::new(private_key))
+            }
+            kem::Mode::DhKemP256 => {
+                let private_key = EcPrivateKey {
+                    private_key: sk,
+                    parameters: None,
+                    public_key: Some(&self.public_key.value),
+                }
+                .to_der()
+                .map_err(|_| HPKEError::InvalidInput)?;
+                (
+                    ID_EC_PUBLIC_KEY,
+                    Some(AnyRef::from(&SECP256R1)),
+                    Zeroizing::new(private_key),
+                )
+            }
+            _ => return Err(HPKEError::InvalidConfig),
+        };
+        let info = PrivateKeyInfo::new(AlgorithmIdentifierRef { oid, parameters }, &private_key);
+        Ok(Zeroizing::new(
+            info.to_der().map_err(|_| HPKEError::InvalidInput)?,
+        ))
+    }
+}
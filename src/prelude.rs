@@ -2,7 +2,11 @@
 //! Include this to get access to all the public functions of HPKE.
 
 pub use super::{Mode as HpkeMode, *};
-pub use crate::aead::{Error as HpkeAeadError, Mode as HpkeAeadMode};
+pub use crate::aead::{
+    AeadTrait as HpkeAeadTrait, AesGcm128 as HpkeAesGcm128, AesGcm256 as HpkeAesGcm256,
+    ChaCha20Poly1305 as HpkeChaCha20Poly1305, Error as HpkeAeadError, Mode as HpkeAeadMode,
+    SymmetricContext,
+};
 pub use crate::kdf::{Error as HpkeKdfError, Mode as HpkeKdfMode};
-pub use crate::kem::{Error as HpkeKemError, Mode as HpkeKemMode};
+pub use crate::kem::{Error as HpkeKemError, Kem as HpkeKem, Mode as HpkeKemMode};
 pub use std::convert::TryFrom;
@@ -0,0 +1,68 @@
+//! Detection of replayed encapsulated secrets for `Hpke::open`.
+
+use std::collections::HashSet;
+
+use crate::{HPKEError, HPKEPrivateKey, HPKEPublicKey, Hpke, Plaintext};
+
+/// A store of the encapsulated secrets a receiver has seen.
+///
+/// Implement this to back the replay detection of `Hpke::open_checked` with
+/// a shared store, e.g. a database. `MemoryReplayStore` keeps the values in
+/// memory.
+pub trait ReplayStore {
+    /// Record the encapsulated secret `enc`.
+    ///
+    /// Returns `true` if `enc` wasn't seen before and `false` if it was.
+    /// Checking and recording has to be atomic so that concurrent receivers
+    /// can't both accept the same `enc`.
+    fn insert(&mut self, enc: &[u8]) -> bool;
+}
+
+/// A `ReplayStore` that keeps all encapsulated secrets in memory.
+#[derive(Debug, Default)]
+pub struct MemoryReplayStore {
+    seen: HashSet<Vec<u8>>,
+}
+
+impl MemoryReplayStore {
+    /// Create an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ReplayStore for MemoryReplayStore {
+    fn insert(&mut self, enc: &[u8]) -> bool {
+        self.seen.insert(enc.to_vec())
+    }
+}
+
+impl Hpke {
+    /// Single shot API like `open` that rejects encapsulated secrets that were
+    /// seen before.
+    ///
+    /// The `enc` is recorded in the `store` after `ct` was opened, so invalid
+    /// messages don't fill the store. If the `store` has seen `enc` already a
+    /// `Replay` error is returned and the plain text is discarded.
+    /// This only detects replays of whole messages to this `store`; any
+    /// further anti-replay mechanism is up to the application.
+    #[allow(clippy::too_many_arguments)]
+    pub fn open_checked<S: ReplayStore + ?Sized>(
+        &self,
+        store: &mut S,
+        enc: &[u8],
+        sk_r: &HPKEPrivateKey,
+        info: &[u8],
+        aad: &[u8],
+        ct: &[u8],
+        psk: Option<&[u8]>,
+        psk_id: Option<&[u8]>,
+        pk_s: Option<&HPKEPublicKey>,
+    ) -> Result<Plaintext, HPKEError> {
+        let ptxt = self.open(enc, sk_r, info, aad, ct, psk, psk_id, pk_s)?;
+        if !store.insert(enc) {
+            return Err(HPKEError::Replay);
+        }
+        Ok(ptxt)
+    }
+}
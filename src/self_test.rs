@@ -0,0 +1,247 @@
+//! Power-on self-test with a fixed known answer per ciphersuite.
+
+use crate::{aead, kdf, kem, util, HPKEError, Hpke, Mode};
+
+/// The Base mode inputs of a known answer.
+pub(crate) struct Inputs {
+    ikm_r: &'static [u8],
+    ikm_e: &'static [u8],
+    info: &'static [u8],
+    aad: &'static [u8],
+    plain_txt: &'static [u8],
+}
+
+/// Fixed inputs for the suites without a draft test vector.
+const SELF_TEST_INPUTS: Inputs = Inputs {
+    ikm_r: b"hpke-rs self test receiver key material",
+    ikm_e: b"hpke-rs self test ephemeral key material",
+    info: b"hpke-rs self test info",
+    aad: b"hpke-rs self test aad",
+    plain_txt: b"hpke-rs self test",
+};
+
+/// The inputs of the first encryption of the Base mode
+/// DHKEM(X25519, HKDF-SHA256), HKDF-SHA256, AES-128-GCM test vector of
+/// draft-irtf-cfrg-hpke-06 (`tests/test_vectors.json`).
+const DRAFT_INPUTS: Inputs = Inputs {
+    ikm_r: &[
+        0xab, 0xd1, 0xbd, 0xbd, 0xbc, 0x6a, 0x93, 0x62, 0x6e, 0xcd, 0x60, 0xce, 0x37, 0x0b, 0xe0,
+        0xa7, 0x69, 0x2a, 0x5b, 0xcd, 0x23, 0xf6, 0x4b, 0xb3, 0x1b, 0x49, 0xd1, 0x73, 0xef, 0x2a,
+        0xe1, 0x42,
+    ],
+    ikm_e: &[
+        0xae, 0xea, 0x7f, 0x66, 0xa2, 0x79, 0x08, 0xed, 0x74, 0xe1, 0x68, 0x3c, 0x78, 0xcd, 0xb2,
+        0xa4, 0xce, 0xcd, 0xd1, 0x8f, 0x13, 0x78, 0x49, 0xb4, 0x85, 0x74, 0xf3, 0x2a, 0x26, 0xfa,
+        0xef, 0x4c,
+    ],
+    info: b"Ode on a Grecian Urn",
+    aad: b"Count-0",
+    plain_txt: b"Beauty is truth, truth beauty",
+};
+
+/// The expected Base mode ciphertexts `(kem, kdf, aead, inputs, ct)` for all
+/// supported suites.
+///
+/// The DHKEM(X25519, HKDF-SHA256), HKDF-SHA256, AES-128-GCM entry is taken
+/// from the draft-irtf-cfrg-hpke-06 test vectors, so it is checked against an
+/// independent implementation. The other suites have no Base mode vector with
+/// a supported KEM there. Their values were generated with this
+/// implementation from `SELF_TEST_INPUTS` and only detect changes of the
+/// build, not bugs that were present when they were generated.
+const EXPECTED_CIPHERTEXTS: &[(kem::Mode, kdf::Mode, aead::Mode, &Inputs, &str)] = &[
+    (
+        kem::Mode::DhKemP256,
+        kdf::Mode::HkdfSha256,
+        aead::Mode::AesGcm128,
+        &SELF_TEST_INPUTS,
+        "a1339609533c379494b79e41a2d5f24e23e25091193e1baa0cd1447e04b8e9a2f9",
+    ),
+    (
+        kem::Mode::DhKemP256,
+        kdf::Mode::HkdfSha256,
+        aead::Mode::AesGcm256,
+        &SELF_TEST_INPUTS,
+        "5cadd004fff318353b7c06cdb18387be17d650d1dde2d4c36028ace2acbda2e324",
+    ),
+    (
+        kem::Mode::DhKemP256,
+        kdf::Mode::HkdfSha256,
+        aead::Mode::ChaCha20Poly1305,
+        &SELF_TEST_INPUTS,
+        "db78437af9544fdc4e7f47e343a0ee7b6213717d48e806ce1901819bd4b6e3317f",
+    ),
+    (
+        kem::Mode::DhKemP256,
+        kdf::Mode::HkdfSha384,
+        aead::Mode::AesGcm128,
+        &SELF_TEST_INPUTS,
+        "3dde6b8147e773c90d90608eb354342c45d4ef8e783c4fe666b1d02ff16d2050f5",
+    ),
+    (
+        kem::Mode::DhKemP256,
+        kdf::Mode::HkdfSha384,
+        aead::Mode::AesGcm256,
+        &SELF_TEST_INPUTS,
+        "e5cbb03ab9470fb2cc4e55afc98a4f7cb4564a1e76869e4fc9161e8cad5a1340a7",
+    ),
+    (
+        kem::Mode::DhKemP256,
+        kdf::Mode::HkdfSha384,
+        aead::Mode::ChaCha20Poly1305,
+        &SELF_TEST_INPUTS,
+        "35c5f0338c143c502b9ad817d5cb2b89fe19a8d1b675e640eea4c7506c27049eab",
+    ),
+    (
+        kem::Mode::DhKemP256,
+        kdf::Mode::HkdfSha512,
+        aead::Mode::AesGcm128,
+        &SELF_TEST_INPUTS,
+        "e9b64ecd0af868b385532b62e921ff818f43c537378f9549242ca7f6b5e3a5b2b6",
+    ),
+    (
+        kem::Mode::DhKemP256,
+        kdf::Mode::HkdfSha512,
+        aead::Mode::AesGcm256,
+        &SELF_TEST_INPUTS,
+        "a883a197de14241630882a3c15048c72e3ffe04c7aa51fcb4253552beaa1f7e976",
+    ),
+    (
+        kem::Mode::DhKemP256,
+        kdf::Mode::HkdfSha512,
+        aead::Mode::ChaCha20Poly1305,
+        &SELF_TEST_INPUTS,
+        "e9fe0a9763a461ab17cd74014eb6d6c4c09382d8009ea2d79f9292d91a8cd4d909",
+    ),
+    (
+        kem::Mode::DhKem25519,
+        kdf::Mode::HkdfSha256,
+        aead::Mode::AesGcm128,
+        &DRAFT_INPUTS,
+        "335d6dc57e081c3cc427820259b5ccbbf92dd36a49cd485ff6efa52eecb05c6b3192c38d7f381fcf54dfbfb4ed",
+    ),
+    (
+        kem::Mode::DhKem25519,
+        kdf::Mode::HkdfSha256,
+        aead::Mode::AesGcm256,
+        &SELF_TEST_INPUTS,
+        "f964fad0394f3c5ed12f1db44d18b9cb3f6012c7b66b1ca946db98f4b49cb24bc3",
+    ),
+    (
+        kem::Mode::DhKem25519,
+        kdf::Mode::HkdfSha256,
+        aead::Mode::ChaCha20Poly1305,
+        &SELF_TEST_INPUTS,
+        "6b9bad1cb3c735b6d3a3c3d04e87564f1027b4b6932a56700e1af4ecb54344a00c",
+    ),
+    (
+        kem::Mode::DhKem25519,
+        kdf::Mode::HkdfSha384,
+        aead::Mode::AesGcm128,
+        &SELF_TEST_INPUTS,
+        "7899d005512f334c3598e237d10c80e4bcfcfb256df9bab430fc8d9bf77712006e",
+    ),
+    (
+        kem::Mode::DhKem25519,
+        kdf::Mode::HkdfSha384,
+        aead::Mode::AesGcm256,
+        &SELF_TEST_INPUTS,
+        "598193ddd2271e89136daebf830f80057866cc5f37157ea6c3a91accbbecca03d1",
+    ),
+    (
+        kem::Mode::DhKem25519,
+        kdf::Mode::HkdfSha384,
+        aead::Mode::ChaCha20Poly1305,
+        &SELF_TEST_INPUTS,
+        "2ec1ca96887e15fcb50eccfe986d51be2c9c8eedbb9e9764cf00a672cd61466049",
+    ),
+    (
+        kem::Mode::DhKem25519,
+        kdf::Mode::HkdfSha512,
+        aead::Mode::AesGcm128,
+        &SELF_TEST_INPUTS,
+        "b79cbd4a5038c2d18f618a0c57f8b5d6c43f184734b9f2000989b336e14de676fe",
+    ),
+    (
+        kem::Mode::DhKem25519,
+        kdf::Mode::HkdfSha512,
+        aead::Mode::AesGcm256,
+        &SELF_TEST_INPUTS,
+        "52c0727f41c314e1c4d6dd6b5663f88c97ca8493c61ec5ab29ffb8a9b583b369fd",
+    ),
+    (
+        kem::Mode::DhKem25519,
+        kdf::Mode::HkdfSha512,
+        aead::Mode::ChaCha20Poly1305,
+        &SELF_TEST_INPUTS,
+        "151b9562452b8e9a121dd223590d6a837e291dc73e658cfa2dca59771425f33cd3",
+    ),
+];
+
+impl Hpke {
+    /// Run a known-answer self-test for the suite of this configuration.
+    ///
+    /// This seals a fixed plain text in Base mode with fixed receiver and ephemeral
+    /// keys, compares the ciphertext to the embedded expected value, and opens
+    /// it again. Call this at startup to detect broken builds.
+    /// For DHKEM(X25519, HKDF-SHA256), HKDF-SHA256, AES-128-GCM the known answer
+    /// is a draft test vector, see `EXPECTED_CIPHERTEXTS`.
+    /// Returns a `SelfTestFailed` error if any step fails.
+    pub fn self_test(&self) -> Result<(), HPKEError> {
+        let (inputs, expected) = self.known_answer()?;
+        self.self_test_with(inputs, &expected)
+    }
+
+    /// Get the embedded known answer for the suite of this configuration.
+    pub(crate) fn known_answer(&self) -> Result<(&'static Inputs, Vec<u8>), HPKEError> {
+        let expected = EXPECTED_CIPHERTEXTS
+            .iter()
+            .find(|(kem_id, kdf_id, aead_id, _, _)| {
+                *kem_id == self.kem_id && *kdf_id == self.kdf_id && *aead_id == self.aead_id
+            })
+            .ok_or(HPKEError::SelfTestFailed)?;
+        Ok((expected.3, util::hex_to_bytes(expected.4)))
+    }
+
+    /// Run the self-test with the `inputs` against the `expected` ciphertext.
+    pub(crate) fn self_test_with(&self, inputs: &Inputs, expected: &[u8]) -> Result<(), HPKEError> {
+        let ct = self.self_test_seal(inputs)?;
+        if ct != expected {
+            return Err(HPKEError::SelfTestFailed);
+        }
+
+        let hpke = Hpke::new(Mode::Base, self.kem_id, self.kdf_id, self.aead_id);
+        let (pk_r, sk_r) = hpke.kem.derive_key_pair(inputs.ikm_r);
+        let (_zz, enc) = hpke
+            .kem
+            .encaps(&pk_r, inputs.ikm_e)
+            .map_err(|_| HPKEError::SelfTestFailed)?;
+        let zz = hpke
+            .kem
+            .decaps(&enc, &sk_r)
+            .map_err(|_| HPKEError::SelfTestFailed)?;
+        let mut context = hpke
+            .key_schedule(&zz, inputs.info, &[], &[])
+            .map_err(|_| HPKEError::SelfTestFailed)?;
+        match context.open(inputs.aad, &ct) {
+            Ok(pt) if pt == inputs.plain_txt => Ok(()),
+            _ => Err(HPKEError::SelfTestFailed),
+        }
+    }
+
+    /// Seal the plain text of the `inputs` in Base mode with the keys derived
+    /// from them.
+    pub(crate) fn self_test_seal(&self, inputs: &Inputs) -> Result<Vec<u8>, HPKEError> {
+        let hpke = Hpke::new(Mode::Base, self.kem_id, self.kdf_id, self.aead_id);
+        let (pk_r, _sk_r) = hpke.kem.derive_key_pair(inputs.ikm_r);
+        let (zz, _enc) = hpke
+            .kem
+            .encaps(&pk_r, inputs.ikm_e)
+            .map_err(|_| HPKEError::SelfTestFailed)?;
+        let mut context = hpke
+            .key_schedule(&zz, inputs.info, &[], &[])
+            .map_err(|_| HPKEError::SelfTestFailed)?;
+        context
+            .seal(inputs.aad, inputs.plain_txt)
+            .map_err(|_| HPKEError::SelfTestFailed)
+    }
+}
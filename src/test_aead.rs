@@ -16,3 +16,140 @@ fn test_aes_gcm_128_self() {
     let ptxt = aead.open(&key, &nonce, &aad, &ctxt).unwrap();
     assert_eq!(&ptxt, msg);
 }
+
+#[test]
+fn test_incremental_aad() {
+    for &mode in &[
+        aead::Mode::AesGcm128,
+        aead::Mode::AesGcm256,
+        aead::Mode::ChaCha20Poly1305,
+    ] {
+        let aead = aead::Aead::new(mode);
+        let key = vec![0x42u8; aead.get_nk()];
+        let nonce = [0x24u8; 12];
+        let msg = b"test message";
+
+        let ctxt = aead.seal(&key, &nonce, b"header|body", msg).unwrap();
+        let ctxt_incremental = aead
+            .seal_incremental(&key, &nonce, &[b"header|", b"body"], msg)
+            .unwrap();
+        assert_eq!(ctxt, ctxt_incremental);
+
+        let ptxt = aead
+            .open_incremental(&key, &nonce, &[b"head", b"er|body"], &ctxt)
+            .unwrap();
+        assert_eq!(&ptxt, msg);
+    }
+}
+
+#[test]
+fn test_symmetric_context() {
+    let key = [0x42u8; 16];
+    let base_nonce = [0x24u8; 12];
+    let mut sender = aead::SymmetricContext::new(aead::Mode::AesGcm128, &key, &base_nonce).unwrap();
+    let mut receiver =
+        aead::SymmetricContext::new(aead::Mode::AesGcm128, &key, &base_nonce).unwrap();
+    let aead = aead::Aead::new(aead::Mode::AesGcm128);
+
+    for seq in 0u32..5 {
+        let msg = format!("test message {}", seq);
+        let ctxt = sender.seal(b"aad", msg.as_bytes()).unwrap();
+
+        // The nonce is the base nonce XORed with the sequence number.
+        let mut nonce = base_nonce;
+        for (n, s) in nonce[8..].iter_mut().zip(seq.to_be_bytes().iter()) {
+            *n ^= s;
+        }
+        assert_eq!(
+            ctxt,
+            aead.seal(&key, &nonce, b"aad", msg.as_bytes()).unwrap()
+        );
+
+        let ptxt = receiver.open(b"aad", &ctxt).unwrap();
+        assert_eq!(ptxt, msg.as_bytes());
+        assert_eq!(receiver.sequence_number(), seq + 1);
+    }
+
+    // Out of order messages don't open.
+    let ctxt = sender.seal(b"aad", b"first").unwrap();
+    let ctxt2 = sender.seal(b"aad", b"second").unwrap();
+    assert!(receiver.open(b"aad", &ctxt2).is_err());
+    assert_eq!(receiver.open(b"aad", &ctxt).unwrap(), b"first");
+
+    assert!(matches!(
+        aead::SymmetricContext::new(aead::Mode::AesGcm128, &[0u8; 32], &base_nonce),
+        Err(aead::Error::InvalidConfig)
+    ));
+    assert!(matches!(
+        aead::SymmetricContext::new(aead::Mode::AesGcm128, &key, &[0u8; 8]),
+        Err(aead::Error::InvalidNonce)
+    ));
+}
+
+#[test]
+fn test_symmetric_context_max_seq() {
+    let key = [0x42u8; 16];
+    let base_nonce = [0x24u8; 12];
+    let mut sender = aead::SymmetricContext::new(aead::Mode::AesGcm128, &key, &base_nonce).unwrap();
+    let mut receiver =
+        aead::SymmetricContext::new(aead::Mode::AesGcm128, &key, &base_nonce).unwrap();
+    sender.set_sequence_number(u32::MAX - 1);
+    receiver.set_sequence_number(u32::MAX - 1);
+
+    // The last sequence number seals and opens one more message.
+    let ctxt = sender.seal(b"aad", b"last message").unwrap();
+    assert_eq!(receiver.open(b"aad", &ctxt).unwrap(), b"last message");
+    assert_eq!(sender.sequence_number(), u32::MAX);
+
+    // After that the context is exhausted instead of wrapping around.
+    assert!(matches!(
+        sender.seal(b"aad", b"one more"),
+        Err(aead::Error::MessageLimitReached)
+    ));
+    assert!(matches!(
+        receiver.open(b"aad", &ctxt),
+        Err(aead::Error::MessageLimitReached)
+    ));
+    assert_eq!(sender.sequence_number(), u32::MAX);
+}
+
+/// The tag check is done in constant time by the backends, which can't be
+/// tested here. This checks the other half of the contract: every tampered
+/// ciphertext fails with the same `OpenError` and releases no plain text.
+#[test]
+fn test_open_failure_contract() {
+    for &mode in &[
+        aead::Mode::AesGcm128,
+        aead::Mode::AesGcm256,
+        aead::Mode::ChaCha20Poly1305,
+    ] {
+        let aead = aead::Aead::new(mode);
+        let key = vec![0x42u8; aead.get_nk()];
+        let nonce = [0x24u8; 12];
+        let ctxt = aead.seal(&key, &nonce, b"aad", b"test message").unwrap();
+
+        for i in 0..ctxt.len() {
+            for &bit in &[0x01u8, 0x80] {
+                let mut tampered = ctxt.clone();
+                tampered[i] ^= bit;
+                assert!(matches!(
+                    aead.open(&key, &nonce, b"aad", &tampered),
+                    Err(aead::Error::OpenError)
+                ));
+                let (body, tag) = tampered.split_at(tampered.len() - 16);
+                assert!(matches!(
+                    aead.open_detached(&key, &nonce, b"aad", body, tag),
+                    Err(aead::Error::OpenError)
+                ));
+            }
+        }
+        assert!(matches!(
+            aead.open(&key, &nonce, b"other aad", &ctxt),
+            Err(aead::Error::OpenError)
+        ));
+        assert!(matches!(
+            aead.open(&key, &nonce, b"aad", &ctxt[..15]),
+            Err(aead::Error::OpenError)
+        ));
+    }
+}
@@ -0,0 +1,181 @@
+use crate::*;
+
+#[test]
+fn test_ciphersuite_encoding() {
+    let hpke = Hpke::new(
+        Mode::Base,
+        kem::Mode::DhKem25519,
+        kdf::Mode::HkdfSha256,
+        aead::Mode::AesGcm128,
+    );
+
+    // The suite ids are encoded as I2OSP(id, 2), i.e. big-endian.
+    let expected = [b'H', b'P', b'K', b'E', 0x00, 0x20, 0x00, 0x01, 0x00, 0x01];
+    assert_eq!(&expected[..], &hpke.get_ciphersuite()[..]);
+}
+
+#[test]
+fn test_aead_error_conversion() {
+    assert_eq!(
+        HPKEError::InvalidNonce,
+        HPKEError::from(aead::Error::InvalidNonce)
+    );
+    assert_eq!(
+        HPKEError::OpenError,
+        HPKEError::from(aead::Error::OpenError)
+    );
+
+    let aead = aead::Aead::new(aead::Mode::AesGcm128);
+    let err = aead.seal(&[0u8; 16], &[0u8; 8], b"", b"").unwrap_err();
+    assert_eq!(HPKEError::InvalidNonce, err.into());
+}
+
+#[test]
+fn test_suite_sizes() {
+    // (aead, Nk, Nn) from the AEAD IDs table of the RFC.
+    let aead_sizes = [
+        (aead::Mode::AesGcm128, 16, 12),
+        (aead::Mode::AesGcm256, 32, 12),
+        (aead::Mode::ChaCha20Poly1305, 32, 12),
+    ];
+    // (kdf, Nh) from the KDF IDs table of the RFC.
+    let kdf_sizes = [
+        (kdf::Mode::HkdfSha256, 32),
+        (kdf::Mode::HkdfSha384, 48),
+        (kdf::Mode::HkdfSha512, 64),
+    ];
+
+    for (kem_id, kdf_id, aead_id) in Hpke::supported_suites() {
+        let hpke = Hpke::new(Mode::Base, kem_id, kdf_id, aead_id);
+        let &(_, nk, nn) = aead_sizes
+            .iter()
+            .find(|(mode, _, _)| *mode == aead_id)
+            .unwrap();
+        let &(_, nh) = kdf_sizes.iter().find(|(mode, _)| *mode == kdf_id).unwrap();
+        assert_eq!(hpke.nk, nk, "Nk for {}", hpke);
+        assert_eq!(hpke.nn, nn, "Nn for {}", hpke);
+        assert_eq!(hpke.nh, nh, "Nh for {}", hpke);
+    }
+}
+
+#[test]
+fn test_self_test() {
+    for (kem_id, kdf_id, aead_id) in Hpke::supported_suites() {
+        let hpke = Hpke::new(Mode::Base, kem_id, kdf_id, aead_id);
+        assert_eq!(hpke.self_test(), Ok(()));
+    }
+
+    // The mode of the configuration doesn't matter.
+    let hpke = Hpke::new(
+        Mode::AuthPsk,
+        kem::Mode::DhKem25519,
+        kdf::Mode::HkdfSha256,
+        aead::Mode::AesGcm128,
+    );
+    assert_eq!(hpke.self_test(), Ok(()));
+
+    // The known answer of this suite is the draft test vector.
+    let (inputs, expected) = hpke.known_answer().unwrap();
+    assert_eq!(
+        expected,
+        util::hex_to_bytes(
+            "335d6dc57e081c3cc427820259b5ccbbf92dd36a49cd485ff6efa52eecb05c6b3192c38d7f381fcf54dfbfb4ed"
+        )
+    );
+
+    // A corrupted expected value fails.
+    let mut expected = hpke.self_test_seal(inputs).unwrap();
+    assert_eq!(hpke.self_test_with(inputs, &expected), Ok(()));
+    expected[0] ^= 1;
+    assert_eq!(
+        hpke.self_test_with(inputs, &expected),
+        Err(HPKEError::SelfTestFailed)
+    );
+}
+
+#[test]
+fn test_combine_shared_secrets() {
+    // A single shared secret is used as is.
+    assert_eq!(
+        kem::combine_shared_secrets(&[b"shared secret"]),
+        b"shared secret"
+    );
+
+    // Different splits of the same bytes combine differently.
+    let combined = kem::combine_shared_secrets(&[b"ab", b"c"]);
+    assert_eq!(combined, b"\x00\x02ab\x00\x01c");
+    assert_ne!(combined, kem::combine_shared_secrets(&[b"a", b"bc"]));
+    assert_ne!(combined, kem::combine_shared_secrets(&[b"abc"]));
+    assert_ne!(
+        kem::combine_shared_secrets(&[b"", b"abc"]),
+        kem::combine_shared_secrets(&[b"abc", b""])
+    );
+}
+
+#[test]
+fn test_seal_consuming() {
+    let hpke = Hpke::new(
+        Mode::Base,
+        kem::Mode::DhKem25519,
+        kdf::Mode::HkdfSha256,
+        aead::Mode::AesGcm128,
+    );
+    let shared_secret = [0x42u8; 32];
+    let mut sender_context = hpke
+        .key_schedule(&shared_secret, b"info", &[], &[])
+        .unwrap();
+    let mut receiver_context = hpke
+        .key_schedule(&shared_secret, b"info", &[], &[])
+        .unwrap();
+
+    // The plain text buffer is zeroized and cleared.
+    let mut plain_txt = b"plain text".to_vec();
+    let ctxt = sender_context
+        .seal_and_zeroize(b"aad", &mut plain_txt)
+        .unwrap();
+    assert!(plain_txt.is_empty());
+    assert_eq!(receiver_context.open(b"aad", &ctxt).unwrap(), b"plain text");
+
+    let ctxt = sender_context
+        .seal_consuming(b"aad", b"plain text".to_vec())
+        .unwrap();
+    assert_eq!(receiver_context.open(b"aad", &ctxt).unwrap(), b"plain text");
+}
+
+#[test]
+fn test_rewrap_zeroizes_plain_text() {
+    let hpke = Hpke::new(
+        Mode::Base,
+        kem::Mode::DhKem25519,
+        kdf::Mode::HkdfSha256,
+        aead::Mode::AesGcm128,
+    );
+    let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+
+    // `rewrap` seals the opened plain text with this and the buffer is
+    // zeroized and cleared afterwards.
+    let mut plain_txt = b"plain text".to_vec();
+    let (enc, ctxt) = hpke
+        .seal_and_zeroize(&pk_r, b"info", b"aad", &mut plain_txt, None, None)
+        .unwrap();
+    assert!(plain_txt.is_empty());
+    assert_eq!(
+        hpke.open(&enc, &sk_r, b"info", b"aad", &ctxt, None, None, None)
+            .unwrap(),
+        b"plain text"
+    );
+
+    // It's zeroized if sealing fails as well.
+    let mut plain_txt = b"plain text".to_vec();
+    assert!(hpke
+        .seal_and_zeroize(
+            &pk_r,
+            b"info",
+            b"aad",
+            &mut plain_txt,
+            Some(&[0x42; 32]),
+            None
+        )
+        .is_err());
+    assert!(plain_txt.is_empty());
+}
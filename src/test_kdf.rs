@@ -21,3 +21,31 @@ fn test_hkdf_sha256() {
     assert_eq!(&expected_prk, &prk);
     assert_eq!(&expected_okm, &okm);
 }
+
+#[test]
+fn test_labeled_expand_multi() {
+    let kdf = kdf::Kdf::new(kdf::Mode::HkdfSha384);
+    let suite_id = b"HPKE\x00\x20\x00\x02\x00\x01";
+    let prk = kdf.labeled_extract(&[], suite_id, "secret", b"shared secret");
+    let info = b"key schedule context";
+
+    let outputs = kdf.labeled_expand_multi(
+        &prk,
+        suite_id,
+        info,
+        &[("key", 16), ("base_nonce", 12), ("exp", 48)],
+    );
+    assert_eq!(outputs.len(), 3);
+    assert_eq!(
+        outputs[0],
+        kdf.labeled_expand(&prk, suite_id, "key", info, 16)
+    );
+    assert_eq!(
+        outputs[1],
+        kdf.labeled_expand(&prk, suite_id, "base_nonce", info, 12)
+    );
+    assert_eq!(
+        outputs[2],
+        kdf.labeled_expand(&prk, suite_id, "exp", info, 48)
+    );
+}
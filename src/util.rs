@@ -11,6 +11,15 @@ pub(crate) fn xor_bytes(a: &[u8], b: &[u8]) -> Vec<u8> {
     a.iter().zip(b).map(|(x, y)| x ^ y).collect()
 }
 
+/// Split `bytes` at `mid`, or return `None` if `bytes` is shorter than `mid`.
+#[inline]
+pub(crate) fn split_at_checked(bytes: &[u8], mid: usize) -> Option<(&[u8], &[u8])> {
+    if bytes.len() < mid {
+        return None;
+    }
+    Some(bytes.split_at(mid))
+}
+
 pub(crate) fn hex_to_bytes(hex: &str) -> Vec<u8> {
     assert!(hex.len() % 2 == 0);
     let mut bytes = Vec::new();
@@ -24,3 +24,448 @@ fn test_serialization() {
     let aead_mode_out: AeadMode = serde_json::from_str(&serialized_mode).unwrap();
     assert_eq!(aead_mode, aead_mode_out);
 }
+
+#[test]
+#[cfg(feature = "pem")]
+fn test_spki_pem() {
+    use hpke::prelude::*;
+
+    let p256_pem = "-----BEGIN PUBLIC KEY-----
+MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEQed+PuyKerkf9774AiCygUio0L0P
+S+VlQxMJZ49QWrXGjzRZKoCZDe5ABQIImtyL+Bo0+Oe9qMVvV9oulS6rkA==
+-----END PUBLIC KEY-----
+";
+    let pk = HPKEPublicKey::from_spki_pem(p256_pem).unwrap();
+    assert_eq!(pk.as_slice().len(), 65);
+    assert_eq!(pk.as_slice()[0], 0x04);
+    assert_eq!(pk.to_spki_pem(HpkeKemMode::DhKemP256).unwrap(), p256_pem);
+
+    let x25519_pem = "-----BEGIN PUBLIC KEY-----
+MCowBQYDK2VuAyEAGW2NfZ1YKzpkXbi/DJDUqFaM8HzT+pBYB9qfddqHvmc=
+-----END PUBLIC KEY-----
+";
+    let pk = HPKEPublicKey::from_spki_pem(x25519_pem).unwrap();
+    assert_eq!(pk.as_slice().len(), 32);
+    assert_eq!(pk.to_spki_pem(HpkeKemMode::DhKem25519).unwrap(), x25519_pem);
+
+    assert_eq!(
+        HPKEPublicKey::from_spki_pem("not a pem").unwrap_err(),
+        HPKEError::InvalidInput
+    );
+}
+
+#[test]
+#[cfg(feature = "audit")]
+fn test_seal_audited() {
+    use hpke::prelude::*;
+
+    let hpke = Hpke::new(
+        HpkeMode::Base,
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::AesGcm128,
+    );
+    let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+    let (enc, mut sender_context) = hpke.setup_sender(&pk_r, b"info", None, None, None).unwrap();
+    let mut receiver_context = hpke
+        .setup_receiver(&enc, &sk_r, b"info", None, None, None)
+        .unwrap();
+
+    let mut nonces: Vec<Vec<u8>> = Vec::new();
+    for _ in 0..10 {
+        let (ctxt, nonce) = sender_context.seal_audited(b"aad", b"plain text").unwrap();
+        assert_eq!(nonce.len(), 12);
+        assert!(!nonces.contains(&nonce));
+        nonces.push(nonce);
+        assert_eq!(receiver_context.open(b"aad", &ctxt).unwrap(), b"plain text");
+    }
+}
+
+#[test]
+#[cfg(feature = "debug-trace")]
+fn test_key_schedule_debug() {
+    use hpke::prelude::*;
+
+    let hpke = Hpke::new(
+        HpkeMode::Psk,
+        HpkeKemMode::DhKemP256,
+        HpkeKdfMode::HkdfSha384,
+        HpkeAeadMode::AesGcm256,
+    );
+    let shared_secret = hpke::random_bytes(32);
+    let psk = hpke::random_bytes(32);
+    let info = b"info";
+    let psk_id = b"psk id";
+
+    let trace = hpke
+        .key_schedule_debug(&shared_secret, info, &psk, psk_id)
+        .unwrap();
+    let context = hpke
+        .key_schedule(&shared_secret, info, &psk, psk_id)
+        .unwrap();
+    assert_eq!(trace.key, context.key());
+    assert_eq!(trace.base_nonce, context.nonce());
+    assert_eq!(trace.exporter_secret, context.exporter_secret());
+    assert_eq!(trace.key_schedule_context.len(), 1 + 48 + 48);
+    assert_eq!(trace.key_schedule_context[0], HpkeMode::Psk as u8);
+    assert_eq!(trace.key_schedule_context[1..49], trace.psk_id_hash[..]);
+    assert_eq!(trace.key_schedule_context[49..], trace.info_hash[..]);
+
+    // The trace applies the same limits as the key schedule.
+    let hpke = hpke.with_limits(HpkeLimits {
+        max_info: 2,
+        ..HpkeLimits::default()
+    });
+    assert_eq!(
+        hpke.key_schedule(&shared_secret, info, &psk, psk_id)
+            .unwrap_err(),
+        HPKEError::InvalidInput
+    );
+    assert_eq!(
+        hpke.key_schedule_debug(&shared_secret, info, &psk, psk_id)
+            .unwrap_err(),
+        HPKEError::InvalidInput
+    );
+}
+
+#[test]
+#[cfg(feature = "debug-trace")]
+fn test_compute_secret() {
+    use hpke::prelude::*;
+
+    for &mode in &[HpkeMode::Base, HpkeMode::Psk] {
+        let hpke = Hpke::new(
+            mode,
+            HpkeKemMode::DhKem25519,
+            HpkeKdfMode::HkdfSha512,
+            HpkeAeadMode::ChaCha20Poly1305,
+        );
+        let shared_secret = hpke::random_bytes(32);
+        let (psk, psk_id): (&[u8], &[u8]) = if mode == HpkeMode::Psk {
+            (&[0x42; 32], b"psk id")
+        } else {
+            (&[], &[])
+        };
+
+        let trace = hpke
+            .key_schedule_debug(&shared_secret, b"info", psk, psk_id)
+            .unwrap();
+        let secret = hpke.compute_secret(&shared_secret, psk);
+        assert_eq!(secret.len(), 64);
+        assert_eq!(secret, trace.secret);
+    }
+}
+
+#[test]
+#[cfg(feature = "digest")]
+fn test_setup_sender_with_transcript() {
+    use hpke::prelude::*;
+    use sha2::{Digest, Sha256, Sha512};
+
+    let hpke = Hpke::new(
+        HpkeMode::Base,
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::AesGcm128,
+    );
+    let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+
+    let mut transcript = Sha256::new();
+    transcript.update(b"client hello");
+    transcript.update(b"server hello");
+    let info = transcript.clone().finalize();
+
+    let (enc, mut sender_context) = hpke
+        .setup_sender_with_transcript(&pk_r, transcript, None, None, None)
+        .unwrap();
+    let mut receiver_context = hpke
+        .setup_receiver(&enc, &sk_r, &info, None, None, None)
+        .unwrap();
+    let ctxt = sender_context.seal(b"aad", b"plain text").unwrap();
+    assert_eq!(receiver_context.open(b"aad", &ctxt).unwrap(), b"plain text");
+
+    // The digest has to match the KDF's hash size.
+    assert_eq!(
+        hpke.setup_sender_with_transcript(&pk_r, Sha512::new(), None, None, None)
+            .map(|_| ())
+            .unwrap_err(),
+        HPKEError::InvalidInput
+    );
+}
+
+#[test]
+#[cfg(feature = "serialization")]
+fn test_hpke_config_serialization() {
+    let hpke = Hpke::new(
+        HpkeMode::AuthPsk,
+        HpkeKemMode::DhKemP256,
+        HpkeKdfMode::HkdfSha384,
+        HpkeAeadMode::ChaCha20Poly1305,
+    );
+    let serialized = serde_json::to_value(&hpke).unwrap();
+    assert_eq!(
+        serialized,
+        serde_json::json!({
+            "mode": "AuthPsk",
+            "kem_id": "DhKemP256",
+            "kdf_id": "HkdfSha384",
+            "aead_id": "ChaCha20Poly1305"
+        })
+    );
+
+    let hpke_out: Hpke = serde_json::from_value(serialized).unwrap();
+    assert_eq!(format!("{}", hpke), format!("{}", hpke_out));
+
+    // The reconstructed configuration is fully functional.
+    let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+    let (sk_s, pk_s) = hpke.generate_key_pair().into_keys();
+    let psk = [0x42u8; 32];
+    let (enc, ctxt) = hpke
+        .seal(
+            &pk_r,
+            b"info",
+            b"aad",
+            b"plain text",
+            Some(&psk),
+            Some(b"psk id"),
+            Some(&sk_s),
+        )
+        .unwrap();
+    let ptxt = hpke_out
+        .open(
+            &enc,
+            &sk_r,
+            b"info",
+            b"aad",
+            &ctxt,
+            Some(&psk),
+            Some(b"psk id"),
+            Some(&pk_s),
+        )
+        .unwrap();
+    assert_eq!(ptxt, b"plain text");
+}
+
+#[test]
+#[cfg(feature = "base64")]
+fn test_seal_base64() {
+    let hpke = Hpke::new(
+        HpkeMode::Base,
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::AesGcm128,
+    );
+    let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+    let (enc, ctxt) = hpke
+        .seal_base64(&pk_r, b"info", b"aad", b"plain text", None, None, None)
+        .unwrap();
+    assert!(!enc.contains(&['+', '/', '='][..]));
+    assert!(!ctxt.contains(&['+', '/', '='][..]));
+
+    let ptxt = hpke
+        .open_base64(&enc, &sk_r, b"info", b"aad", &ctxt, None, None, None)
+        .unwrap();
+    assert_eq!(ptxt, b"plain text");
+
+    // Malformed base64 is rejected.
+    for (enc, ctxt) in &[
+        ("not base64!", ctxt.as_str()),
+        (enc.as_str(), "not base64!"),
+        (enc.as_str(), &format!("{}=", ctxt)),
+    ] {
+        assert_eq!(
+            hpke.open_base64(enc, &sk_r, b"info", b"aad", ctxt, None, None, None),
+            Err(HPKEError::InvalidInput)
+        );
+    }
+}
+
+#[test]
+#[cfg(feature = "bytes")]
+fn test_seal_bytes() {
+    use bytes::Bytes;
+
+    let hpke = Hpke::new(
+        HpkeMode::Base,
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::AesGcm128,
+    );
+    let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+    let (enc, mut sender_context) = hpke.setup_sender(&pk_r, b"info", None, None, None).unwrap();
+    let mut receiver_context = hpke
+        .setup_receiver(&enc, &sk_r, b"info", None, None, None)
+        .unwrap();
+
+    for msg in &[&b"first"[..], &b""[..], &[0x42u8; 1000][..]] {
+        let ctxt = sender_context
+            .seal_bytes(b"aad", Bytes::copy_from_slice(msg))
+            .unwrap();
+        assert_eq!(ctxt.len(), msg.len() + 16);
+        let ptxt = receiver_context.open_bytes(b"aad", ctxt).unwrap();
+        assert_eq!(&ptxt[..], *msg);
+    }
+
+    let ctxt = sender_context
+        .seal_bytes(b"aad", Bytes::from_static(b"plain text"))
+        .unwrap();
+    assert_eq!(
+        receiver_context.open_bytes(b"other aad", ctxt),
+        Err(HPKEError::OpenError)
+    );
+}
+
+#[test]
+#[cfg(feature = "pkcs8")]
+fn test_pkcs8() {
+    use hpke::test_util::hex_to_bytes;
+
+    // The X25519 private key of Alice from RFC 7748, section 6.1.
+    let der = hex_to_bytes(
+        "302e020100300506032b656e04220420\
+         77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a",
+    );
+    let key_pair = HPKEKeyPair::from_pkcs8_der(&der, HpkeKemMode::DhKem25519).unwrap();
+    assert_eq!(
+        key_pair.public_key().as_slice(),
+        &hex_to_bytes("8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a")[..]
+    );
+    assert_eq!(
+        &key_pair.to_pkcs8_der(HpkeKemMode::DhKem25519).unwrap()[..],
+        &der[..]
+    );
+
+    // The key doesn't match other KEMs.
+    assert_eq!(
+        HPKEKeyPair::from_pkcs8_der(&der, HpkeKemMode::DhKemP256).unwrap_err(),
+        HPKEError::InvalidInput
+    );
+    assert_eq!(
+        HPKEKeyPair::from_pkcs8_der(&der, HpkeKemMode::DhKem448).unwrap_err(),
+        HPKEError::InvalidConfig
+    );
+    assert_eq!(
+        HPKEKeyPair::from_pkcs8_der(&der[..der.len() - 1], HpkeKemMode::DhKem25519).unwrap_err(),
+        HPKEError::InvalidInput
+    );
+
+    // P256 keys round-trip.
+    let hpke = Hpke::new(
+        HpkeMode::Base,
+        HpkeKemMode::DhKemP256,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::AesGcm128,
+    );
+    let key_pair = hpke.generate_key_pair();
+    let der = key_pair.to_pkcs8_der(HpkeKemMode::DhKemP256).unwrap();
+    let decoded = HPKEKeyPair::from_pkcs8_der(&der, HpkeKemMode::DhKemP256).unwrap();
+    assert_eq!(decoded.private_key(), key_pair.private_key());
+    assert_eq!(decoded.public_key(), key_pair.public_key());
+}
+
+#[test]
+#[cfg(feature = "bench")]
+fn test_benchmark_setup() {
+    let hpke = Hpke::new(
+        HpkeMode::AuthPsk,
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::AesGcm128,
+    );
+    assert!(hpke.benchmark_setup(3).unwrap() > std::time::Duration::ZERO);
+    assert_eq!(hpke.benchmark_setup(0).unwrap(), std::time::Duration::ZERO);
+}
+
+#[test]
+#[cfg(feature = "backend-ring")]
+fn test_backend_ring() {
+    use evercrypt::prelude::{Aead, AeadMode};
+
+    let nonce = [0x4eu8; 12];
+    let aad = b"additional data";
+    let ptxt = b"a plain text of a few bytes";
+    let aeads: [(Box<dyn HpkeAeadTrait>, AeadMode, usize); 3] = [
+        (Box::new(HpkeAesGcm128::new()), AeadMode::Aes128Gcm, 16),
+        (Box::new(HpkeAesGcm256::new()), AeadMode::Aes256Gcm, 32),
+        (
+            Box::new(HpkeChaCha20Poly1305::new()),
+            AeadMode::Chacha20Poly1305,
+            32,
+        ),
+    ];
+    for (aead, mode, key_len) in aeads.iter() {
+        let key = vec![0x6bu8; *key_len];
+        let ctxt = aead.seal(&key, &nonce, aad, ptxt).unwrap();
+
+        let (mut expected, tag) = Aead::new(*mode, &key)
+            .unwrap()
+            .encrypt(ptxt, &nonce, aad)
+            .unwrap();
+        expected.extend_from_slice(&tag);
+        assert_eq!(ctxt, expected);
+        assert_eq!(aead.open(&key, &nonce, aad, &ctxt).unwrap(), ptxt);
+
+        let mut tampered = ctxt.clone();
+        tampered[0] ^= 1;
+        assert!(aead.open(&key, &nonce, aad, &tampered).is_err());
+    }
+}
+
+#[test]
+#[cfg(feature = "replay-guard")]
+fn test_open_checked() {
+    let hpke = Hpke::new(
+        HpkeMode::Base,
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::AesGcm128,
+    );
+    let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+    let mut store = MemoryReplayStore::new();
+    let (enc, ct) = hpke
+        .seal(&pk_r, b"info", b"aad", b"message", None, None, None)
+        .unwrap();
+
+    // A forgery with the same enc doesn't block the genuine message.
+    let mut forged = ct.clone();
+    forged[0] ^= 1;
+    assert_eq!(
+        hpke.open_checked(&mut store, &enc, &sk_r, b"info", b"aad", &forged, None, None, None)
+            .unwrap_err(),
+        HPKEError::OpenError
+    );
+
+    let ptxt = hpke
+        .open_checked(
+            &mut store, &enc, &sk_r, b"info", b"aad", &ct, None, None, None,
+        )
+        .unwrap();
+    assert_eq!(ptxt, b"message");
+    assert_eq!(
+        hpke.open_checked(&mut store, &enc, &sk_r, b"info", b"aad", &ct, None, None, None)
+            .unwrap_err(),
+        HPKEError::Replay
+    );
+
+    // Other messages are still accepted.
+    let (enc, ct) = hpke
+        .seal(&pk_r, b"info", b"aad", b"message", None, None, None)
+        .unwrap();
+    let store: &mut dyn ReplayStore = &mut store;
+    assert!(hpke
+        .open_checked(store, &enc, &sk_r, b"info", b"aad", &ct, None, None, None)
+        .is_ok());
+}
+
+#[test]
+#[cfg(feature = "serialization")]
+fn test_deserialize_unsupported_suite() {
+    // An unimplemented KEM from an untrusted config is an error, not a panic.
+    let config = serde_json::json!({
+        "mode": "Base",
+        "kem_id": "DhKemP384",
+        "kdf_id": "HkdfSha384",
+        "aead_id": "AesGcm256"
+    });
+    assert!(serde_json::from_value::<Hpke>(config).is_err());
+}
@@ -608,3 +608,2939 @@ generate_test_case!(
     HpkeKdfMode::HkdfSha512,
     HpkeAeadMode::ChaCha20Poly1305
 );
+
+/// The suite of the tests below that don't depend on the algorithms.
+fn base_hpke() -> Hpke {
+    Hpke::new(
+        HpkeMode::Base,
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::AesGcm128,
+    )
+}
+
+/// Set up a sender and a receiver context for `info` with a fresh receiver
+/// key pair.
+fn base_pair<'a>(hpke: &'a Hpke, info: &[u8]) -> (Context<'a>, Context<'a>) {
+    let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+    let (enc, sender_context) = hpke.setup_sender(&pk_r, info, None, None, None).unwrap();
+    let receiver_context = hpke
+        .setup_receiver(&enc, &sk_r, info, None, None, None)
+        .unwrap();
+    (sender_context, receiver_context)
+}
+
+#[test]
+fn test_random_bytes() {
+    let a = hpke::random_bytes(32);
+    let b = hpke::random_bytes(32);
+    assert_eq!(a.len(), 32);
+    assert_eq!(b.len(), 32);
+    assert_ne!(a, b);
+    assert!(hpke::random_bytes(0).is_empty());
+}
+
+#[test]
+fn test_seal_to_writer() {
+    let hpke = base_hpke();
+    let info = b"HPKE self test info";
+    let aad = b"HPKE self test aad";
+    let plain_txt = b"HPKE self test plain text";
+
+    let (mut sender_context, mut receiver_context) = base_pair(&hpke, info);
+
+    let mut buffer = Vec::new();
+    sender_context
+        .seal_to_writer(aad, plain_txt, &mut buffer)
+        .unwrap();
+    let mut reader = std::io::Cursor::new(buffer);
+    let ptxt = receiver_context.open_from_reader(aad, &mut reader).unwrap();
+    assert_eq!(ptxt, plain_txt);
+}
+
+#[test]
+fn test_supported_suites() {
+    let suites = Hpke::supported_suites();
+    assert_eq!(suites.len(), 2 * 3 * 3);
+    assert!(suites.contains(&(
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::AesGcm128
+    )));
+    assert!(!suites
+        .iter()
+        .any(|&(kem, _, _)| kem == HpkeKemMode::DhKem448));
+}
+
+#[test]
+fn test_open_malformed_input() {
+    for &kem_mode in &[HpkeKemMode::DhKem25519, HpkeKemMode::DhKemP256] {
+        let hpke = Hpke::new(
+            HpkeMode::Base,
+            kem_mode,
+            HpkeKdfMode::HkdfSha256,
+            HpkeAeadMode::AesGcm128,
+        );
+        let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+        let (enc, ctxt) = hpke
+            .seal(&pk_r, b"info", b"aad", b"plain text", None, None, None)
+            .unwrap();
+
+        // Truncated ciphertexts and encapsulations.
+        for len in 0..ctxt.len() {
+            assert!(hpke
+                .open(&enc, &sk_r, b"info", b"aad", &ctxt[..len], None, None, None)
+                .is_err());
+        }
+        for len in 0..enc.len() {
+            assert!(hpke
+                .open(&enc[..len], &sk_r, b"info", b"aad", &ctxt, None, None, None)
+                .is_err());
+        }
+
+        // Random garbage.
+        for _ in 0..200 {
+            let lengths = hpke::random_bytes(4);
+            let enc = hpke::random_bytes(lengths[0] as usize % 100);
+            let ct = hpke::random_bytes(lengths[1] as usize % 100);
+            let aad = hpke::random_bytes(lengths[2] as usize % 100);
+            let info = hpke::random_bytes(lengths[3] as usize % 100);
+            assert!(hpke
+                .open(&enc, &sk_r, &info, &aad, &ct, None, None, None)
+                .is_err());
+        }
+    }
+}
+
+#[test]
+fn test_ratchet() {
+    let hpke = Hpke::new(
+        HpkeMode::Base,
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::ChaCha20Poly1305,
+    );
+    let info = b"HPKE self test info";
+    let aad = b"HPKE self test aad";
+    let plain_txt = b"HPKE self test plain text";
+
+    let (mut sender_context, mut receiver_context) = base_pair(&hpke, info);
+
+    for _ in 0..3 {
+        for _ in 0..5 {
+            let ctxt = sender_context.seal(aad, plain_txt).unwrap();
+            let ptxt = receiver_context.open(aad, &ctxt).unwrap();
+            assert_eq!(ptxt, plain_txt);
+        }
+
+        let old_key = sender_context.key().to_vec();
+        let old_exporter_secret = sender_context.exporter_secret().to_vec();
+        sender_context.ratchet().unwrap();
+        receiver_context.ratchet().unwrap();
+        assert_ne!(old_key, sender_context.key());
+        assert_ne!(old_exporter_secret, sender_context.exporter_secret());
+        assert_eq!(sender_context.key(), receiver_context.key());
+        assert_eq!(sender_context.sequence_number(), 0);
+    }
+
+    // A context that didn't ratchet can't open any more.
+    let (mut sender_context, mut receiver_context) = base_pair(&hpke, info);
+    sender_context.ratchet().unwrap();
+    let ctxt = sender_context.seal(aad, plain_txt).unwrap();
+    assert_eq!(
+        receiver_context.open(aad, &ctxt).unwrap_err(),
+        HPKEError::OpenError
+    );
+}
+
+#[test]
+fn test_binding_info() {
+    let hpke = base_hpke();
+    let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+    let info = b"HPKE self test info";
+    let aad = b"HPKE self test aad";
+    let plain_txt = b"HPKE self test plain text";
+
+    let (enc, mut sender_context) = hpke
+        .setup_sender_binding_info(&pk_r, info, None, None, None)
+        .unwrap();
+    let mut receiver_context = hpke
+        .setup_receiver_binding_info(&enc, &sk_r, info, None, None, None)
+        .unwrap();
+    let ctxt = sender_context.seal(aad, plain_txt).unwrap();
+    assert_eq!(receiver_context.open(aad, &ctxt).unwrap(), plain_txt);
+
+    // A receiver that doesn't bind the info can't open.
+    let (enc, mut sender_context) = hpke
+        .setup_sender_binding_info(&pk_r, info, None, None, None)
+        .unwrap();
+    let mut receiver_context = hpke
+        .setup_receiver(&enc, &sk_r, info, None, None, None)
+        .unwrap();
+    let ctxt = sender_context.seal(aad, plain_txt).unwrap();
+    assert_eq!(
+        receiver_context.open(aad, &ctxt).unwrap_err(),
+        HPKEError::OpenError
+    );
+
+    // A receiver binding a different info can't open.
+    let (enc, mut sender_context) = hpke
+        .setup_sender_binding_info(&pk_r, info, None, None, None)
+        .unwrap();
+    let mut receiver_context = hpke
+        .setup_receiver_binding_info(&enc, &sk_r, b"other info", None, None, None)
+        .unwrap();
+    let ctxt = sender_context.seal(aad, plain_txt).unwrap();
+    assert_eq!(
+        receiver_context.open(aad, &ctxt).unwrap_err(),
+        HPKEError::OpenError
+    );
+}
+
+#[test]
+fn test_export_labeled() {
+    let hpke = base_hpke();
+    let info = b"HPKE self test info";
+    let exporter_context = b"HPKE self test exporter context";
+
+    let (sender_context, receiver_context) = base_pair(&hpke, info);
+
+    let a = sender_context
+        .export_labeled(b"protocol a", exporter_context, 32)
+        .unwrap();
+    let b = sender_context
+        .export_labeled(b"protocol b", exporter_context, 32)
+        .unwrap();
+    assert_ne!(a, b);
+    assert_ne!(a, sender_context.export(exporter_context, 32));
+    assert_eq!(
+        a,
+        receiver_context
+            .export_labeled(b"protocol a", exporter_context, 32)
+            .unwrap()
+    );
+
+    // The label is length prefixed.
+    assert_ne!(
+        sender_context.export_labeled(b"ab", b"c", 32).unwrap(),
+        sender_context.export_labeled(b"a", b"bc", 32).unwrap()
+    );
+
+    // Labels that don't fit the length prefix and too long exports are
+    // rejected.
+    assert_eq!(
+        sender_context.export_labeled(&vec![0u8; 1 << 16], exporter_context, 32),
+        Err(HPKEError::InvalidInput)
+    );
+    assert_eq!(
+        sender_context
+            .export_labeled(b"protocol a", exporter_context, 255 * 32)
+            .unwrap()
+            .len(),
+        255 * 32
+    );
+    assert_eq!(
+        sender_context.export_labeled(b"protocol a", exporter_context, 255 * 32 + 1),
+        Err(HPKEError::InvalidInput)
+    );
+}
+
+#[test]
+fn test_expected_ciphertext_len() {
+    let hpke = base_hpke();
+    let (mut sender_context, _) = base_pair(&hpke, b"info");
+
+    for &len in &[0usize, 1, 15, 16, 17, 1000] {
+        let plain_txt = vec![0x42u8; len];
+        let ctxt = sender_context.seal(b"aad", &plain_txt).unwrap();
+        assert_eq!(ctxt.len(), hpke.expected_ciphertext_len(len));
+        assert_eq!(ctxt.len(), sender_context.expected_ciphertext_len(len));
+    }
+}
+
+#[test]
+fn test_nsecret() {
+    for &kem_mode in &[HpkeKemMode::DhKem25519, HpkeKemMode::DhKemP256] {
+        let hpke = Hpke::new(
+            HpkeMode::Base,
+            kem_mode,
+            HpkeKdfMode::HkdfSha256,
+            HpkeAeadMode::AesGcm128,
+        );
+        assert_eq!(hpke.nsecret(), 32);
+
+        let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+        let (enc, sender_zz, _sender_context) = hpke
+            .setup_sender_with_shared_secret(&pk_r, b"info", None, None, None)
+            .unwrap();
+        let (receiver_zz, _receiver_context) = hpke
+            .setup_receiver_with_shared_secret(&enc, &sk_r, b"info", None, None, None)
+            .unwrap();
+        assert_eq!(sender_zz.len(), hpke.nsecret());
+        assert_eq!(sender_zz, receiver_zz);
+    }
+}
+
+#[test]
+fn test_open_vectored() {
+    let hpke = base_hpke();
+    let info = b"HPKE self test info";
+    let plain_txt = b"HPKE self test plain text";
+
+    let (mut sender_context, mut receiver_context) = base_pair(&hpke, info);
+
+    let ctxt = sender_context
+        .seal(b"header|body|trailer", plain_txt)
+        .unwrap();
+    let ptxt = receiver_context
+        .open_vectored(&[b"header|", b"body|", b"trailer"], &ctxt)
+        .unwrap();
+    assert_eq!(ptxt, plain_txt);
+
+    let ctxt = sender_context
+        .seal(b"header|body|trailer", plain_txt)
+        .unwrap();
+    assert_eq!(
+        receiver_context
+            .open_vectored(&[b"header|", b"trailer"], &ctxt)
+            .unwrap_err(),
+        HPKEError::OpenError
+    );
+}
+
+#[test]
+fn test_psk_errors() {
+    let psk = [0x42u8; 32];
+    let psk_id = b"psk id";
+
+    let hpke = base_hpke();
+    let (_sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+    assert_eq!(
+        hpke.setup_sender(&pk_r, b"info", Some(&psk), Some(psk_id), None)
+            .unwrap_err(),
+        HPKEError::PskMismatch {
+            expected_psk: false,
+            got_psk: true
+        }
+    );
+
+    let hpke = Hpke::new(
+        HpkeMode::Psk,
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::AesGcm128,
+    );
+    assert_eq!(
+        hpke.seal(&pk_r, b"info", b"aad", b"plain text", None, None, None)
+            .unwrap_err(),
+        HPKEError::PskMismatch {
+            expected_psk: true,
+            got_psk: false
+        }
+    );
+    assert_eq!(
+        hpke.setup_sender(&pk_r, b"info", Some(&psk), None, None)
+            .unwrap_err(),
+        HPKEError::InconsistentPsk
+    );
+}
+
+#[test]
+fn test_explicit_nonce() {
+    let hpke = base_hpke();
+    let info = b"HPKE self test info";
+    let aad = b"HPKE self test aad";
+    let plain_txt = b"HPKE self test plain text";
+
+    let (sender_context, receiver_context) = base_pair(&hpke, info);
+
+    let nonce = hpke::random_bytes(12);
+    let ctxt = sender_context
+        .seal_with_nonce(&nonce, aad, plain_txt)
+        .unwrap();
+    let ptxt = receiver_context
+        .open_with_nonce(&nonce, aad, &ctxt)
+        .unwrap();
+    assert_eq!(ptxt, plain_txt);
+    assert_eq!(sender_context.sequence_number(), 0);
+    assert_eq!(receiver_context.sequence_number(), 0);
+
+    assert_eq!(
+        sender_context
+            .seal_with_nonce(&nonce[..8], aad, plain_txt)
+            .unwrap_err(),
+        HPKEError::InvalidNonce
+    );
+}
+
+#[test]
+fn test_seal_incremental_aad() {
+    let hpke = Hpke::new(
+        HpkeMode::Base,
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::AesGcm256,
+    );
+    let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+    let info = b"HPKE self test info";
+    let plain_txt = b"HPKE self test plain text";
+
+    let (enc, mut sender_context) = hpke
+        .setup_sender_binding_info(&pk_r, info, None, None, None)
+        .unwrap();
+    let mut receiver_context = hpke
+        .setup_receiver_binding_info(&enc, &sk_r, info, None, None, None)
+        .unwrap();
+
+    let ctxt = sender_context
+        .seal_incremental_aad(&[b"header|", b"body"], plain_txt)
+        .unwrap();
+    let ptxt = receiver_context.open(b"header|body", &ctxt).unwrap();
+    assert_eq!(ptxt, plain_txt);
+}
+
+#[test]
+fn test_refresh_aead_key() {
+    let hpke = Hpke::new(
+        HpkeMode::Base,
+        HpkeKemMode::DhKemP256,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::AesGcm128,
+    );
+    let info = b"HPKE self test info";
+    let aad = b"HPKE self test aad";
+    let plain_txt = b"HPKE self test plain text";
+
+    let (mut sender_context, mut receiver_context) = base_pair(&hpke, info);
+    let ctxt = sender_context.seal(aad, plain_txt).unwrap();
+    receiver_context.open(aad, &ctxt).unwrap();
+
+    let old_key = sender_context.key().to_vec();
+    let old_export = sender_context.export(b"context", 32);
+    sender_context.refresh_aead_key(b"epoch 1").unwrap();
+    receiver_context.refresh_aead_key(b"epoch 1").unwrap();
+    assert_ne!(old_key, sender_context.key());
+    assert_eq!(old_export, sender_context.export(b"context", 32));
+    assert_eq!(sender_context.sequence_number(), 0);
+
+    for _ in 0..3 {
+        let ctxt = sender_context.seal(aad, plain_txt).unwrap();
+        assert_eq!(receiver_context.open(aad, &ctxt).unwrap(), plain_txt);
+    }
+
+    // Different labels give different keys.
+    let key = sender_context.key().to_vec();
+    sender_context.refresh_aead_key(b"epoch 2").unwrap();
+    receiver_context.refresh_aead_key(b"epoch 3").unwrap();
+    assert_ne!(key, sender_context.key());
+    let ctxt = sender_context.seal(aad, plain_txt).unwrap();
+    assert_eq!(
+        receiver_context.open(aad, &ctxt).unwrap_err(),
+        HPKEError::OpenError
+    );
+
+    // Refreshing twice with the same label doesn't reuse a key and nonce.
+    let key = sender_context.key().to_vec();
+    let nonce = sender_context.nonce().to_vec();
+    sender_context.refresh_aead_key(b"epoch 2").unwrap();
+    assert_ne!(key, sender_context.key());
+    assert_ne!(nonce, sender_context.nonce());
+    assert_eq!(sender_context.sequence_number(), 0);
+}
+
+#[test]
+fn test_export_zeroizing() {
+    let hpke = base_hpke();
+    let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+    let info = b"HPKE self test info";
+    let exporter_context = b"HPKE self test exporter context";
+
+    let (enc, sender_context) = hpke.setup_sender(&pk_r, info, None, None, None).unwrap();
+    let exported: Zeroizing<Vec<u8>> = sender_context.export_zeroizing(exporter_context, 32);
+    assert_eq!(*exported, sender_context.export(exporter_context, 32));
+
+    let (enc_single, sender_exported) = hpke
+        .send_export_zeroizing(&pk_r, info, None, None, None, exporter_context, 32)
+        .unwrap();
+    let receiver_exported = hpke
+        .receiver_export_zeroizing(
+            &enc_single,
+            &sk_r,
+            info,
+            None,
+            None,
+            None,
+            exporter_context,
+            32,
+        )
+        .unwrap();
+    assert_eq!(&sender_exported[..], &receiver_exported[..]);
+    assert_ne!(enc, enc_single);
+}
+
+#[test]
+fn test_open_trial() {
+    let hpke = base_hpke();
+    let (sk_0, _pk_0) = hpke.generate_key_pair().into_keys();
+    let (sk_1, pk_1) = hpke.generate_key_pair().into_keys();
+    let (sk_2, _pk_2) = hpke.generate_key_pair().into_keys();
+    let info = b"HPKE self test info";
+    let aad = b"HPKE self test aad";
+    let plain_txt = b"HPKE self test plain text";
+
+    let (enc, ctxt) = hpke
+        .seal(&pk_1, info, aad, plain_txt, None, None, None)
+        .unwrap();
+    let (index, ptxt) = hpke
+        .open_trial(
+            &enc,
+            &[&sk_0, &sk_1, &sk_2],
+            info,
+            aad,
+            &ctxt,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    assert_eq!(index, 1);
+    assert_eq!(ptxt, plain_txt);
+
+    assert_eq!(
+        hpke.open_trial(&enc, &[&sk_0, &sk_2], info, aad, &ctxt, None, None, None)
+            .unwrap_err(),
+        HPKEError::OpenError
+    );
+    assert_eq!(
+        hpke.open_trial(&enc, &[], info, aad, &ctxt, None, None, None)
+            .unwrap_err(),
+        HPKEError::OpenError
+    );
+
+    // The first matching key is selected.
+    let (index, ptxt) = hpke
+        .open_trial(
+            &enc,
+            &[&sk_0, &sk_1, &sk_1],
+            info,
+            aad,
+            &ctxt,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    assert_eq!(index, 1);
+    assert_eq!(ptxt, plain_txt);
+
+    // Errors that every candidate has in common are passed on.
+    assert_eq!(
+        hpke.open_trial(
+            &enc,
+            &[&sk_0, &sk_1, &sk_2],
+            info,
+            aad,
+            &ctxt,
+            Some(&[0x42u8; 32]),
+            Some(b"psk id"),
+            None
+        )
+        .unwrap_err(),
+        HPKEError::PskMismatch {
+            expected_psk: false,
+            got_psk: true
+        }
+    );
+}
+
+#[test]
+fn test_validate_message() {
+    for &kem_mode in &[HpkeKemMode::DhKem25519, HpkeKemMode::DhKemP256] {
+        let hpke = Hpke::new(
+            HpkeMode::Base,
+            kem_mode,
+            HpkeKdfMode::HkdfSha256,
+            HpkeAeadMode::AesGcm128,
+        );
+        let (_sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+        let (enc, ctxt) = hpke
+            .seal(&pk_r, b"info", b"aad", b"", None, None, None)
+            .unwrap();
+
+        assert_eq!(hpke.validate_message(&enc, &ctxt), Ok(()));
+        assert_eq!(
+            hpke.validate_message(&enc[1..], &ctxt),
+            Err(HPKEError::InvalidInput)
+        );
+        assert_eq!(
+            hpke.validate_message(&enc, &ctxt[1..]),
+            Err(HPKEError::InvalidInput)
+        );
+    }
+}
+
+#[test]
+fn test_key_schedule_into() {
+    let hpke = Hpke::new(
+        HpkeMode::Psk,
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::AesGcm128,
+    );
+    let psk = [0x42u8; 32];
+    let psk_id = b"psk id";
+
+    let mut context = hpke
+        .key_schedule(&[1u8; 32], b"first info", &psk, psk_id)
+        .unwrap();
+    let _ctxt = context.seal(b"aad", b"plain text").unwrap();
+    assert_eq!(context.sequence_number(), 1);
+
+    hpke.key_schedule_into(&mut context, &[2u8; 32], b"second info", &psk, psk_id)
+        .unwrap();
+    let fresh = hpke
+        .key_schedule(&[2u8; 32], b"second info", &psk, psk_id)
+        .unwrap();
+    assert_eq!(context.key(), fresh.key());
+    assert_eq!(context.nonce(), fresh.nonce());
+    assert_eq!(context.exporter_secret(), fresh.exporter_secret());
+    assert_eq!(context.sequence_number(), 0);
+
+    // Invalid inputs leave the context untouched.
+    assert_eq!(
+        hpke.key_schedule_into(&mut context, &[3u8; 32], b"info", &psk, &[]),
+        Err(HPKEError::InconsistentPsk)
+    );
+    assert_eq!(context.key(), fresh.key());
+
+    // Contexts can be reused across suites with different key sizes.
+    let hpke_256 = Hpke::new(
+        HpkeMode::Psk,
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha512,
+        HpkeAeadMode::AesGcm256,
+    );
+    hpke_256
+        .key_schedule_into(&mut context, &[2u8; 32], b"info", &psk, psk_id)
+        .unwrap();
+    let fresh = hpke_256
+        .key_schedule(&[2u8; 32], b"info", &psk, psk_id)
+        .unwrap();
+    assert_eq!(context.key(), fresh.key());
+    assert_eq!(context.nonce(), fresh.nonce());
+    assert_eq!(context.exporter_secret(), fresh.exporter_secret());
+}
+
+#[test]
+fn test_export_array() {
+    let hpke = base_hpke();
+    let (sender_context, receiver_context) = base_pair(&hpke, b"info");
+
+    let key: [u8; 32] = sender_context.export_array(b"chacha key").unwrap();
+    assert_eq!(&key[..], &sender_context.export(b"chacha key", 32)[..]);
+    assert_eq!(key, receiver_context.export_array(b"chacha key").unwrap());
+
+    let iv: [u8; 12] = sender_context.export_array(b"iv").unwrap();
+    assert_eq!(&iv[..], &sender_context.export(b"iv", 12)[..]);
+
+    assert_eq!(
+        sender_context.export_array::<8161>(b"too long"),
+        Err(HPKEError::InvalidInput)
+    );
+}
+
+#[test]
+fn test_invalid_private_keys() {
+    // All-zero X25519 sender key in auth mode.
+    let hpke = Hpke::new(
+        HpkeMode::Auth,
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::AesGcm128,
+    );
+    let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+    let zero_sk = HPKEPrivateKey::new(vec![0u8; 32]);
+    assert_eq!(
+        hpke.setup_sender(&pk_r, b"info", None, None, Some(&zero_sk))
+            .unwrap_err(),
+        HPKEError::InvalidInput
+    );
+
+    // All-zero X25519 receiver key.
+    let (sk_s, pk_s) = hpke.generate_key_pair().into_keys();
+    let (enc, _context) = hpke
+        .setup_sender(&pk_r, b"info", None, None, Some(&sk_s))
+        .unwrap();
+    assert_eq!(
+        hpke.setup_receiver(&enc, &zero_sk, b"info", None, None, Some(&pk_s))
+            .unwrap_err(),
+        HPKEError::InvalidInput
+    );
+    assert!(hpke
+        .setup_receiver(&enc, &sk_r, b"info", None, None, Some(&pk_s))
+        .is_ok());
+
+    // P256 scalars that are zero or not smaller than the group order.
+    let hpke = Hpke::new(
+        HpkeMode::Base,
+        HpkeKemMode::DhKemP256,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::AesGcm128,
+    );
+    let (_sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+    let (enc, _context) = hpke.setup_sender(&pk_r, b"info", None, None, None).unwrap();
+    for sk in &[vec![0u8; 32], vec![0xffu8; 32]] {
+        let sk = HPKEPrivateKey::new(sk.clone());
+        assert_eq!(
+            hpke.setup_receiver(&enc, &sk, b"info", None, None, None)
+                .unwrap_err(),
+            HPKEError::InvalidInput
+        );
+    }
+}
+
+#[test]
+fn test_remaining_messages() {
+    let hpke = base_hpke();
+    let (mut context, _) = base_pair(&hpke, b"info");
+
+    let full_range = u32::MAX as u128;
+    assert_eq!(context.remaining_messages(), full_range);
+    for i in 1..=5 {
+        let _ctxt = context.seal(b"aad", b"plain text").unwrap();
+        assert_eq!(context.remaining_messages(), full_range - i);
+    }
+}
+
+#[test]
+fn test_derive_key_pair_path() {
+    for &kem_mode in &[HpkeKemMode::DhKem25519, HpkeKemMode::DhKemP256] {
+        let hpke = Hpke::new(
+            HpkeMode::Base,
+            kem_mode,
+            HpkeKdfMode::HkdfSha256,
+            HpkeAeadMode::AesGcm128,
+        );
+        let master = [0x42u8; 32];
+
+        let key_pair = hpke.derive_key_pair_path(&master, &[44, 0, 7]).unwrap();
+        let same = hpke.derive_key_pair_path(&master, &[44, 0, 7]).unwrap();
+        assert_eq!(key_pair.private_key(), same.private_key());
+        assert_eq!(key_pair.public_key(), same.public_key());
+
+        for path in &[
+            &[][..],
+            &[44][..],
+            &[44, 0][..],
+            &[44, 0, 8][..],
+            &[7, 0, 44][..],
+        ] {
+            let other = hpke.derive_key_pair_path(&master, path).unwrap();
+            assert_ne!(key_pair.public_key(), other.public_key());
+        }
+        let other = hpke
+            .derive_key_pair_path(&[0x43u8; 32], &[44, 0, 7])
+            .unwrap();
+        assert_ne!(key_pair.public_key(), other.public_key());
+
+        assert_eq!(
+            hpke.derive_key_pair_path(&master[..16], &[44]).unwrap_err(),
+            HPKEError::InvalidInput
+        );
+    }
+}
+
+#[test]
+fn test_setup_and_seal() {
+    let hpke = base_hpke();
+    let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+    let (enc, ctxt, mut sender_context) = hpke
+        .setup_and_seal(&pk_r, b"info", b"aad", b"first", None, None, None)
+        .unwrap();
+    assert_eq!(sender_context.sequence_number(), 1);
+
+    let mut receiver_context = hpke
+        .setup_receiver(&enc, &sk_r, b"info", None, None, None)
+        .unwrap();
+    assert_eq!(receiver_context.open(b"aad", &ctxt).unwrap(), b"first");
+
+    let ctxt = sender_context.seal(b"aad", b"second").unwrap();
+    assert_eq!(receiver_context.open(b"aad", &ctxt).unwrap(), b"second");
+}
+
+#[test]
+fn test_unsupported_suite() {
+    for &kdf_mode in &[
+        HpkeKdfMode::HkdfSha256,
+        HpkeKdfMode::HkdfSha384,
+        HpkeKdfMode::HkdfSha512,
+    ] {
+        assert!(Hpke::try_new(
+            HpkeMode::Base,
+            HpkeKemMode::DhKem25519,
+            kdf_mode,
+            HpkeAeadMode::AesGcm128,
+        )
+        .is_ok());
+    }
+
+    // KEMs that aren't implemented are an error, not a panic.
+    for &(kem_mode, kdf_mode) in &[
+        (HpkeKemMode::DhKemP384, HpkeKdfMode::HkdfSha384),
+        (HpkeKemMode::DhKemP521, HpkeKdfMode::HkdfSha256),
+        (HpkeKemMode::DhKem448, HpkeKdfMode::HkdfSha512),
+    ] {
+        assert_eq!(
+            Hpke::try_new(HpkeMode::Base, kem_mode, kdf_mode, HpkeAeadMode::AesGcm128,)
+                .unwrap_err(),
+            HPKEError::UnsupportedSuite
+        );
+    }
+}
+
+#[test]
+fn test_split_framed() {
+    for &kem_mode in &[HpkeKemMode::DhKem25519, HpkeKemMode::DhKemP256] {
+        let hpke = Hpke::new(
+            HpkeMode::Base,
+            kem_mode,
+            HpkeKdfMode::HkdfSha256,
+            HpkeAeadMode::AesGcm128,
+        );
+        let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+        let (enc, ctxt) = hpke
+            .seal(&pk_r, b"info", b"aad", b"plain text", None, None, None)
+            .unwrap();
+        let framed = [&enc[..], &ctxt[..]].concat();
+
+        let (split_enc, split_ctxt) = hpke.split_framed(&framed).unwrap();
+        assert_eq!(split_enc, &enc[..]);
+        assert_eq!(split_ctxt, &ctxt[..]);
+        let ptxt = hpke
+            .open(
+                split_enc, &sk_r, b"info", b"aad", split_ctxt, None, None, None,
+            )
+            .unwrap();
+        assert_eq!(ptxt, b"plain text");
+
+        assert_eq!(
+            hpke.split_framed(&enc[..enc.len() - 1]).unwrap_err(),
+            HPKEError::InvalidInput
+        );
+    }
+}
+
+#[test]
+fn test_with_rng() {
+    use rand_chacha::rand_core::SeedableRng;
+
+    let seeded_hpke =
+        || base_hpke().with_rng(Box::new(rand_chacha::ChaCha20Rng::from_seed([7u8; 32])));
+    let hpke_a = seeded_hpke();
+    let hpke_b = seeded_hpke();
+
+    let (sk_a, pk_a) = hpke_a.generate_key_pair().into_keys();
+    let (sk_b, pk_b) = hpke_b.generate_key_pair().into_keys();
+    assert_eq!(sk_a, sk_b);
+    assert_eq!(pk_a, pk_b);
+
+    let sealed_a = hpke_a
+        .seal(&pk_a, b"info", b"aad", b"plain text", None, None, None)
+        .unwrap();
+    let sealed_b = hpke_b
+        .seal(&pk_b, b"info", b"aad", b"plain text", None, None, None)
+        .unwrap();
+    assert_eq!(sealed_a, sealed_b);
+
+    // The next values differ because the RNG advanced.
+    let sealed_c = hpke_a
+        .seal(&pk_a, b"info", b"aad", b"plain text", None, None, None)
+        .unwrap();
+    assert_ne!(sealed_a.0, sealed_c.0);
+
+    let ptxt = hpke_b
+        .open(
+            &sealed_a.0,
+            &sk_b,
+            b"info",
+            b"aad",
+            &sealed_a.1,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    assert_eq!(ptxt, b"plain text");
+}
+
+#[test]
+fn test_verify_export() {
+    let hpke = base_hpke();
+    let (sender_context, receiver_context) = base_pair(&hpke, b"info");
+
+    let confirmation = sender_context.export(b"key confirmation", 32);
+    assert!(receiver_context.verify_export(b"key confirmation", &confirmation));
+    assert!(!receiver_context.verify_export(b"other context", &confirmation));
+
+    let mut wrong = confirmation.clone();
+    wrong[31] ^= 1;
+    assert!(!receiver_context.verify_export(b"key confirmation", &wrong));
+    assert!(!receiver_context.verify_export(b"key confirmation", &confirmation[..16]));
+
+    // Empty and short values never verify, even though they are the correct
+    // prefix of the export.
+    assert!(!receiver_context.verify_export(b"key confirmation", &[]));
+    let short = sender_context.export(b"key confirmation", 31);
+    assert!(!receiver_context.verify_export(b"key confirmation", &short));
+    let long = sender_context.export(b"key confirmation", 64);
+    assert!(receiver_context.verify_export(b"key confirmation", &long));
+}
+
+#[test]
+fn test_setup_sender_auth_with_cert() {
+    let hpke = Hpke::new(
+        HpkeMode::Auth,
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::AesGcm128,
+    );
+    let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+    let (sk_s, pk_s) = hpke.generate_key_pair().into_keys();
+
+    // A stub "certificate" that contains the sender's public key.
+    let cert = pk_s.as_slice().to_vec();
+    let chain: &[&[u8]] = &[&cert, b"root"];
+    let (enc, mut sender_context) = hpke
+        .setup_sender_auth_with_cert(&pk_r, b"info", None, None, &sk_s, chain, |pk, chain| {
+            chain[0] == pk.as_slice()
+        })
+        .unwrap();
+    let ctxt = sender_context.seal(b"aad", b"plain text").unwrap();
+    let mut receiver_context = hpke
+        .setup_receiver(&enc, &sk_r, b"info", None, None, Some(&pk_s))
+        .unwrap();
+    assert_eq!(receiver_context.open(b"aad", &ctxt).unwrap(), b"plain text");
+
+    // A chain for a different key is rejected.
+    let (_, other_pk) = hpke.generate_key_pair().into_keys();
+    let other_cert = other_pk.as_slice().to_vec();
+    let chain: &[&[u8]] = &[&other_cert, b"root"];
+    assert_eq!(
+        hpke.setup_sender_auth_with_cert(&pk_r, b"info", None, None, &sk_s, chain, |pk, chain| {
+            chain[0] == pk.as_slice()
+        })
+        .unwrap_err(),
+        HPKEError::CertificateRejected
+    );
+
+    let base_hpke = base_hpke();
+    assert_eq!(
+        base_hpke
+            .setup_sender_auth_with_cert(&pk_r, b"info", None, None, &sk_s, &[], |_, _| true)
+            .unwrap_err(),
+        HPKEError::InvalidConfig
+    );
+}
+
+#[test]
+fn test_enc_to_public_key() {
+    for &kem_mode in &[HpkeKemMode::DhKem25519, HpkeKemMode::DhKemP256] {
+        let hpke = Hpke::new(
+            HpkeMode::Base,
+            kem_mode,
+            HpkeKdfMode::HkdfSha256,
+            HpkeAeadMode::AesGcm128,
+        );
+        let (_sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+        let (enc, _context) = hpke.setup_sender(&pk_r, b"info", None, None, None).unwrap();
+
+        let pk_e = hpke.enc_to_public_key(&enc).unwrap();
+        assert_eq!(pk_e.as_slice(), &enc[..]);
+
+        // The ephemeral public key is a valid recipient key.
+        assert!(hpke
+            .seal(&pk_e, b"info", b"aad", b"plain text", None, None, None)
+            .is_ok());
+
+        assert_eq!(
+            hpke.enc_to_public_key(&enc[1..]).unwrap_err(),
+            HPKEError::InvalidInput
+        );
+    }
+
+    // Not a point on P256.
+    let hpke = Hpke::new(
+        HpkeMode::Base,
+        HpkeKemMode::DhKemP256,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::AesGcm128,
+    );
+    let mut invalid = vec![0x04u8];
+    invalid.extend_from_slice(&[0x01u8; 64]);
+    assert_eq!(
+        hpke.enc_to_public_key(&invalid).unwrap_err(),
+        HPKEError::InvalidInput
+    );
+}
+
+#[test]
+fn test_limits() {
+    let limits = HpkeLimits {
+        max_info: 8,
+        max_aad: 4,
+        max_psk: 40,
+        max_plaintext: 16,
+        ..HpkeLimits::default()
+    };
+    let hpke = Hpke::new(
+        HpkeMode::Psk,
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::AesGcm128,
+    )
+    .with_limits(limits);
+    let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+    let psk = [0x42u8; 32];
+    let psk_id = b"psk id";
+
+    // info
+    assert_eq!(
+        hpke.setup_sender(&pk_r, b"too long info", Some(&psk), Some(psk_id), None)
+            .unwrap_err(),
+        HPKEError::InvalidInput
+    );
+
+    // psk
+    assert_eq!(
+        hpke.setup_sender(&pk_r, b"info", Some(&[0x42u8; 41]), Some(psk_id), None)
+            .unwrap_err(),
+        HPKEError::InvalidInput
+    );
+
+    let (enc, mut sender_context) = hpke
+        .setup_sender(&pk_r, b"info", Some(&psk), Some(psk_id), None)
+        .unwrap();
+    let mut receiver_context = hpke
+        .setup_receiver(&enc, &sk_r, b"info", Some(&psk), Some(psk_id), None)
+        .unwrap();
+
+    // aad
+    assert_eq!(
+        sender_context.seal(b"long aad", b"plain text"),
+        Err(HPKEError::InvalidInput)
+    );
+    assert_eq!(
+        sender_context.seal_incremental_aad(&[b"aad", b"aad"], b"plain text"),
+        Err(HPKEError::InvalidInput)
+    );
+
+    // plain text
+    assert_eq!(
+        sender_context.seal(b"aad", &[0u8; 17]),
+        Err(HPKEError::InvalidInput)
+    );
+
+    // Rejected calls don't advance the sequence number.
+    assert_eq!(sender_context.sequence_number(), 0);
+    let ctxt = sender_context.seal(b"aad", &[0u8; 16]).unwrap();
+    assert_eq!(
+        receiver_context.open(b"long aad", &ctxt),
+        Err(HPKEError::InvalidInput)
+    );
+    assert_eq!(receiver_context.open(b"aad", &ctxt).unwrap(), vec![0u8; 16]);
+
+    // The default limits are permissive.
+    let hpke = base_hpke().with_limits(HpkeLimits::default());
+    let (_enc, mut context) = hpke
+        .setup_sender(&pk_r, &[0u8; 1000], None, None, None)
+        .unwrap();
+    assert!(context.seal(&[0u8; 1000], &[0u8; 1000]).is_ok());
+}
+
+#[test]
+fn test_test_key_pair() {
+    for &kem_mode in &[HpkeKemMode::DhKem25519, HpkeKemMode::DhKemP256] {
+        let hpke = Hpke::new(
+            HpkeMode::Base,
+            kem_mode,
+            HpkeKdfMode::HkdfSha256,
+            HpkeAeadMode::AesGcm128,
+        );
+        let alice = hpke.test_key_pair("alice");
+        assert_eq!(
+            alice.private_key(),
+            hpke.test_key_pair("alice").private_key()
+        );
+        assert_eq!(alice.public_key(), hpke.test_key_pair("alice").public_key());
+
+        let bob = hpke.test_key_pair("bob");
+        assert_ne!(alice.private_key(), bob.private_key());
+        assert_ne!(alice.public_key(), bob.public_key());
+
+        let (enc, ctxt) = hpke
+            .seal(
+                bob.public_key(),
+                b"info",
+                b"aad",
+                b"plain text",
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        let ptxt = hpke
+            .open(
+                &enc,
+                bob.private_key(),
+                b"info",
+                b"aad",
+                &ctxt,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        assert_eq!(ptxt, b"plain text");
+    }
+}
+
+#[test]
+fn test_export_reader() {
+    use std::io::Read;
+
+    for &kdf_mode in &[HpkeKdfMode::HkdfSha256, HpkeKdfMode::HkdfSha512] {
+        let hpke = Hpke::new(
+            HpkeMode::Base,
+            HpkeKemMode::DhKem25519,
+            kdf_mode,
+            HpkeAeadMode::AesGcm128,
+        );
+        let (_sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+        let (_enc, context) = hpke.setup_sender(&pk_r, b"info", None, None, None).unwrap();
+
+        for &length in &[0, 1, 32, 100, 1000] {
+            let mut reader = context.export_reader(b"keystream", length).unwrap();
+            let mut streamed = Vec::new();
+            let mut chunk = [0u8; 7];
+            loop {
+                let n = reader.read(&mut chunk).unwrap();
+                if n == 0 {
+                    break;
+                }
+                streamed.extend_from_slice(&chunk[..n]);
+            }
+            assert_eq!(streamed, context.export(b"keystream", length));
+        }
+
+        let max_len = 255
+            * if kdf_mode == HpkeKdfMode::HkdfSha256 {
+                32
+            } else {
+                64
+            };
+        let mut reader = context.export_reader(b"keystream", max_len).unwrap();
+        let mut streamed = Vec::new();
+        reader.read_to_end(&mut streamed).unwrap();
+        assert_eq!(streamed.len(), max_len);
+        assert_eq!(
+            context
+                .export_reader(b"keystream", max_len + 1)
+                .unwrap_err(),
+            HPKEError::InvalidInput
+        );
+    }
+}
+
+#[test]
+fn test_negotiate() {
+    let preference = [
+        (
+            HpkeKemMode::DhKem25519,
+            HpkeKdfMode::HkdfSha256,
+            HpkeAeadMode::ChaCha20Poly1305,
+        ),
+        (
+            HpkeKemMode::DhKemP256,
+            HpkeKdfMode::HkdfSha256,
+            HpkeAeadMode::AesGcm256,
+        ),
+        (
+            HpkeKemMode::DhKemP256,
+            HpkeKdfMode::HkdfSha256,
+            HpkeAeadMode::AesGcm128,
+        ),
+    ];
+
+    // Overlapping offers pick the most preferred common suite.
+    let peer = [(0x0010, 0x0001, 0x0001), (0x0010, 0x0001, 0x0002)];
+    assert_eq!(Hpke::negotiate(&peer, &preference), Some(preference[1]));
+    let peer = [(0x0010, 0x0001, 0x0001), (0x0020, 0x0001, 0x0003)];
+    assert_eq!(Hpke::negotiate(&peer, &preference), Some(preference[0]));
+
+    // Disjoint offers and unknown ids don't match.
+    let peer = [(0x0020, 0x0003, 0x0001), (0x0030, 0x0001, 0x0001)];
+    assert_eq!(Hpke::negotiate(&peer, &preference), None);
+    assert_eq!(Hpke::negotiate(&[], &preference), None);
+
+    // Preferred suites that this build doesn't support are skipped.
+    let preference = [
+        (
+            HpkeKemMode::DhKem448,
+            HpkeKdfMode::HkdfSha512,
+            HpkeAeadMode::AesGcm256,
+        ),
+        (
+            HpkeKemMode::DhKem25519,
+            HpkeKdfMode::HkdfSha512,
+            HpkeAeadMode::AesGcm256,
+        ),
+    ];
+    let peer = [(0x0021, 0x0003, 0x0002), (0x0020, 0x0003, 0x0002)];
+    assert_eq!(Hpke::negotiate(&peer, &preference), Some(preference[1]));
+}
+
+#[test]
+fn test_typed_context() {
+    let hpke = Hpke::new(
+        HpkeMode::Base,
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::ChaCha20Poly1305,
+    );
+    let shared_secret = [0x42u8; 32];
+    let mut dynamic = hpke
+        .key_schedule(&shared_secret, b"info", &[], &[])
+        .unwrap();
+    let mut typed = TypedContext::<HpkeChaCha20Poly1305>::new(
+        hpke.key_schedule(&shared_secret, b"info", &[], &[])
+            .unwrap(),
+    )
+    .unwrap();
+    let mut receiver = TypedContext::<HpkeChaCha20Poly1305>::new(
+        hpke.key_schedule(&shared_secret, b"info", &[], &[])
+            .unwrap(),
+    )
+    .unwrap();
+
+    for i in 0..3u8 {
+        let msg = [i; 33];
+        let ctxt = typed.seal(b"aad", &msg).unwrap();
+        assert_eq!(ctxt, dynamic.seal(b"aad", &msg).unwrap());
+        assert_eq!(receiver.open(b"aad", &ctxt).unwrap(), msg);
+    }
+    assert_eq!(typed.context().sequence_number(), 3);
+    assert_eq!(
+        receiver.open(b"aad", &dynamic.seal(b"other aad", b"").unwrap()),
+        Err(HPKEError::OpenError)
+    );
+
+    let context = hpke
+        .key_schedule(&shared_secret, b"info", &[], &[])
+        .unwrap();
+    assert_eq!(
+        TypedContext::<HpkeAesGcm128>::new(context).unwrap_err(),
+        HPKEError::InvalidConfig
+    );
+}
+
+#[test]
+fn test_rekey_with_psk() {
+    let hpke = Hpke::new(
+        HpkeMode::Psk,
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::AesGcm128,
+    );
+    let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+    let psk = [0x42u8; 32];
+    let (enc, zz_s, sender_context) = hpke
+        .setup_sender_with_shared_secret(&pk_r, b"info", Some(&psk), Some(b"psk 1"), None)
+        .unwrap();
+    let (zz_r, _receiver_context) = hpke
+        .setup_receiver_with_shared_secret(&enc, &sk_r, b"info", Some(&psk), Some(b"psk 1"), None)
+        .unwrap();
+
+    let new_psk = [0x43u8; 32];
+    let mut new_sender_context = hpke
+        .rekey_with_psk(&zz_s, b"info", &new_psk, b"psk 2")
+        .unwrap();
+    let mut new_receiver_context = hpke
+        .rekey_with_psk(&zz_r, b"info", &new_psk, b"psk 2")
+        .unwrap();
+    assert_ne!(new_sender_context.key(), sender_context.key());
+    assert_ne!(new_sender_context.nonce(), sender_context.nonce());
+    assert_ne!(
+        new_sender_context.exporter_secret(),
+        sender_context.exporter_secret()
+    );
+
+    let ctxt = new_sender_context.seal(b"aad", b"plain text").unwrap();
+    assert_eq!(
+        new_receiver_context.open(b"aad", &ctxt).unwrap(),
+        b"plain text"
+    );
+
+    let base_hpke = base_hpke();
+    assert_eq!(
+        base_hpke
+            .rekey_with_psk(&zz_s, b"info", &new_psk, b"psk 2")
+            .unwrap_err(),
+        HPKEError::InvalidConfig
+    );
+}
+
+#[test]
+fn test_config_accessors() {
+    let hpke = Hpke::new(
+        HpkeMode::AuthPsk,
+        HpkeKemMode::DhKemP256,
+        HpkeKdfMode::HkdfSha384,
+        HpkeAeadMode::ChaCha20Poly1305,
+    );
+    assert_eq!(hpke.mode(), HpkeMode::AuthPsk);
+    assert_eq!(hpke.kem_id(), HpkeKemMode::DhKemP256);
+    assert_eq!(hpke.kdf_id(), HpkeKdfMode::HkdfSha384);
+    assert_eq!(hpke.aead_id(), HpkeAeadMode::ChaCha20Poly1305);
+}
+
+#[test]
+fn test_setup_dual() {
+    let hpke = base_hpke();
+    let second_hpke = Hpke::new(
+        HpkeMode::Base,
+        HpkeKemMode::DhKemP256,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::AesGcm128,
+    );
+    let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+    let (sk_r_second, pk_r_second) = second_hpke.generate_key_pair().into_keys();
+
+    let (enc, mut sender_context) = hpke
+        .setup_sender_dual(
+            HpkeKemMode::DhKemP256,
+            &pk_r,
+            &pk_r_second,
+            b"info",
+            None,
+            None,
+        )
+        .unwrap();
+    assert_eq!(enc.len(), 32 + 65);
+    let mut receiver_context = hpke
+        .setup_receiver_dual(
+            HpkeKemMode::DhKemP256,
+            &enc,
+            &sk_r,
+            &sk_r_second,
+            b"info",
+            None,
+            None,
+        )
+        .unwrap();
+    let ctxt = sender_context.seal(b"aad", b"plain text").unwrap();
+    assert_eq!(receiver_context.open(b"aad", &ctxt).unwrap(), b"plain text");
+
+    // Both receiver keys are needed.
+    let (other_sk_r_second, _) = second_hpke.generate_key_pair().into_keys();
+    let mut wrong_context = hpke
+        .setup_receiver_dual(
+            HpkeKemMode::DhKemP256,
+            &enc,
+            &sk_r,
+            &other_sk_r_second,
+            b"info",
+            None,
+            None,
+        )
+        .unwrap();
+    assert_eq!(wrong_context.open(b"aad", &ctxt), Err(HPKEError::OpenError));
+
+    assert_eq!(
+        hpke.setup_receiver_dual(
+            HpkeKemMode::DhKemP256,
+            &enc[1..],
+            &sk_r,
+            &sk_r_second,
+            b"info",
+            None,
+            None,
+        )
+        .unwrap_err(),
+        HPKEError::InvalidInput
+    );
+    assert_eq!(
+        hpke.setup_sender_dual(
+            HpkeKemMode::DhKem448,
+            &pk_r,
+            &pk_r_second,
+            b"info",
+            None,
+            None,
+        )
+        .unwrap_err(),
+        HPKEError::InvalidConfig
+    );
+}
+
+#[test]
+fn test_seal_iter() {
+    let hpke = Hpke::new(
+        HpkeMode::Base,
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::ChaCha20Poly1305,
+    );
+    let (mut sender_context, mut receiver_context) = base_pair(&hpke, b"info");
+
+    let frames = vec![
+        (b"aad 0".to_vec(), b"frame 0".to_vec()),
+        (b"aad 1".to_vec(), b"frame 1".to_vec()),
+        (b"aad 2".to_vec(), b"frame 2".to_vec()),
+    ];
+    let ctxts = sender_context
+        .seal_iter(frames.clone().into_iter())
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(ctxts.len(), 3);
+    assert_eq!(sender_context.remaining_messages() + 3, u32::MAX as u128);
+
+    for ((aad, ptxt), ctxt) in frames.iter().zip(ctxts.iter()) {
+        assert_eq!(&receiver_context.open(aad, ctxt).unwrap(), ptxt);
+    }
+}
+
+#[test]
+fn test_seal_detached() {
+    let hpke = Hpke::new(
+        HpkeMode::Base,
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::AesGcm256,
+    );
+    let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+    let (enc, mut sender_context) = hpke.setup_sender(&pk_r, b"info", None, None, None).unwrap();
+    let mut other_sender_context = hpke
+        .setup_receiver(&enc, &sk_r, b"info", None, None, None)
+        .unwrap();
+    let mut receiver_context = hpke
+        .setup_receiver(&enc, &sk_r, b"info", None, None, None)
+        .unwrap();
+
+    let (ctxt, tag) = sender_context.seal_detached(b"aad", b"plain text").unwrap();
+    assert_eq!(tag.len(), 16);
+    assert_eq!(ctxt.len(), b"plain text".len());
+    let combined = other_sender_context.seal(b"aad", b"plain text").unwrap();
+    let mut reassembled = ctxt.clone();
+    reassembled.extend_from_slice(&tag);
+    assert_eq!(reassembled, combined);
+
+    let mut wrong_tag = tag.clone();
+    wrong_tag[0] ^= 1;
+    assert_eq!(
+        receiver_context.open_detached(b"aad", &ctxt, &wrong_tag),
+        Err(HPKEError::OpenError)
+    );
+    assert_eq!(
+        receiver_context.open_detached(b"aad", &ctxt, &tag[1..]),
+        Err(HPKEError::OpenError)
+    );
+    assert_eq!(
+        receiver_context.open_detached(b"aad", &ctxt, &tag).unwrap(),
+        b"plain text"
+    );
+}
+
+#[test]
+fn test_empty_psk() {
+    let hpke = base_hpke();
+    let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+    let empty_psk: &[u8] = &[];
+
+    // An empty PSK with a PSK id is inconsistent.
+    assert_eq!(
+        hpke.setup_sender(&pk_r, b"info", Some(empty_psk), Some(b"psk id"), None)
+            .unwrap_err(),
+        HPKEError::InconsistentPsk
+    );
+
+    // An empty PSK without PSK id is the same as no PSK.
+    let (enc, sender_context) = hpke
+        .setup_sender(&pk_r, b"info", Some(empty_psk), None, None)
+        .unwrap();
+    let receiver_context = hpke
+        .setup_receiver(&enc, &sk_r, b"info", None, None, None)
+        .unwrap();
+    assert_eq!(
+        sender_context.export(b"context", 32),
+        receiver_context.export(b"context", 32)
+    );
+
+    let psk_hpke = Hpke::new(
+        HpkeMode::Psk,
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::AesGcm128,
+    );
+    assert_eq!(
+        psk_hpke
+            .setup_receiver(&enc, &sk_r, b"info", Some(empty_psk), Some(b"psk id"), None)
+            .unwrap_err(),
+        HPKEError::InconsistentPsk
+    );
+    assert_eq!(
+        psk_hpke
+            .setup_receiver(&enc, &sk_r, b"info", Some(empty_psk), None, None)
+            .unwrap_err(),
+        HPKEError::PskMismatch {
+            expected_psk: true,
+            got_psk: false
+        }
+    );
+}
+
+#[test]
+fn test_handshake() {
+    let hpke = Hpke::new(
+        HpkeMode::Base,
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::ChaCha20Poly1305,
+    );
+    let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+    let handshake = hpke
+        .encode_handshake(&pk_r, b"info", b"aad", b"first flight", None, None, None)
+        .unwrap();
+    assert_eq!(handshake.len(), 7 + 2 + 32 + 4 + b"first flight".len() + 16);
+    assert_eq!(&handshake[..7], &[0x00, 0x00, 0x20, 0x00, 0x01, 0x00, 0x03]);
+
+    let (ptxt, receiver_context) = hpke
+        .decode_handshake(&sk_r, &handshake, b"info", b"aad", None, None, None)
+        .unwrap();
+    assert_eq!(ptxt, b"first flight");
+    assert_eq!(receiver_context.export(b"context", 32).len(), 32);
+
+    // Truncated and extended messages are rejected.
+    for len in [0, 6, 8, 40, handshake.len() - 1].iter() {
+        assert_eq!(
+            hpke.decode_handshake(&sk_r, &handshake[..*len], b"info", b"aad", None, None, None)
+                .unwrap_err(),
+            HPKEError::InvalidInput
+        );
+    }
+    let mut extended = handshake.clone();
+    extended.push(0);
+    assert_eq!(
+        hpke.decode_handshake(&sk_r, &extended, b"info", b"aad", None, None, None)
+            .unwrap_err(),
+        HPKEError::InvalidInput
+    );
+
+    // A wrong length prefix is rejected.
+    let mut wrong_enc_len = handshake.clone();
+    wrong_enc_len[8] = 33;
+    assert_eq!(
+        hpke.decode_handshake(&sk_r, &wrong_enc_len, b"info", b"aad", None, None, None)
+            .unwrap_err(),
+        HPKEError::InvalidInput
+    );
+
+    // A message for a different suite is rejected.
+    let mut wrong_suite = handshake.clone();
+    wrong_suite[6] = 0x01;
+    assert_eq!(
+        hpke.decode_handshake(&sk_r, &wrong_suite, b"info", b"aad", None, None, None)
+            .unwrap_err(),
+        HPKEError::InvalidConfig
+    );
+
+    // A modified ciphertext fails to open.
+    let mut wrong_ctxt = handshake;
+    let last = wrong_ctxt.len() - 1;
+    wrong_ctxt[last] ^= 1;
+    assert_eq!(
+        hpke.decode_handshake(&sk_r, &wrong_ctxt, b"info", b"aad", None, None, None)
+            .unwrap_err(),
+        HPKEError::OpenError
+    );
+}
+
+#[test]
+fn test_export_aead() {
+    let hpke = Hpke::new(
+        HpkeMode::Base,
+        HpkeKemMode::DhKemP256,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::AesGcm128,
+    );
+    let (sender_context, receiver_context) = base_pair(&hpke, b"info");
+
+    let mut sender_channel = sender_context
+        .export_aead(HpkeAeadMode::ChaCha20Poly1305, b"data channel")
+        .unwrap();
+    let mut receiver_channel = receiver_context
+        .export_aead(HpkeAeadMode::ChaCha20Poly1305, b"data channel")
+        .unwrap();
+    for i in 0..3u8 {
+        let ctxt = sender_channel.seal(b"aad", &[i; 10]).unwrap();
+        assert_eq!(receiver_channel.open(b"aad", &ctxt).unwrap(), [i; 10]);
+    }
+
+    // A different exporter context yields a different key.
+    let mut other_channel = receiver_context
+        .export_aead(HpkeAeadMode::ChaCha20Poly1305, b"other channel")
+        .unwrap();
+    let ctxt = sender_channel.seal(b"aad", b"plain text").unwrap();
+    assert!(other_channel.open(b"aad", &ctxt).is_err());
+}
+
+#[test]
+fn test_peek_open() {
+    let hpke = base_hpke();
+    let (mut sender_context, mut receiver_context) = base_pair(&hpke, b"info");
+
+    let ctxt_0 = sender_context.seal(b"aad", b"message 0").unwrap();
+    let ctxt_1 = sender_context.seal(b"aad", b"message 1").unwrap();
+
+    // Out of order ciphertexts fail without touching the sequence number.
+    assert_eq!(
+        receiver_context.peek_open(b"aad", &ctxt_1),
+        Err(HPKEError::OpenError)
+    );
+    assert_eq!(
+        receiver_context.peek_open(b"aad", &ctxt_0).unwrap(),
+        b"message 0"
+    );
+    assert_eq!(receiver_context.sequence_number(), 0);
+
+    assert_eq!(
+        receiver_context.open(b"aad", &ctxt_0).unwrap(),
+        b"message 0"
+    );
+    assert_eq!(
+        receiver_context.peek_open(b"aad", &ctxt_1).unwrap(),
+        b"message 1"
+    );
+    assert_eq!(receiver_context.sequence_number(), 1);
+}
+
+#[test]
+fn test_open_early_data() {
+    let hpke = Hpke::new(
+        HpkeMode::Psk,
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::AesGcm128,
+    );
+    let psk = [0x42u8; 32];
+    let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+    let (enc, ctxt) = hpke
+        .seal(
+            &pk_r,
+            b"info",
+            b"aad",
+            b"early data",
+            Some(&psk),
+            Some(b"ticket"),
+            None,
+        )
+        .unwrap();
+
+    // Opening the same early data twice works, i.e. it can be replayed.
+    for _ in 0..2 {
+        let (ptxt, replay_risk) = hpke
+            .open_early_data(
+                &enc,
+                &sk_r,
+                b"info",
+                Some(&psk),
+                Some(b"ticket"),
+                b"aad",
+                &ctxt,
+                None,
+            )
+            .unwrap();
+        assert_eq!(ptxt, b"early data");
+        assert_eq!(replay_risk, ReplayRisk::Unchecked);
+    }
+}
+
+#[test]
+fn test_into_duplex() {
+    let hpke = Hpke::new(
+        HpkeMode::Base,
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::ChaCha20Poly1305,
+    );
+    let (initiator_context, responder_context) = base_pair(&hpke, b"info");
+
+    let (mut initiator_send, mut initiator_recv) = initiator_context.into_duplex(true);
+    let (mut responder_send, mut responder_recv) = responder_context.into_duplex(false);
+
+    for i in 0..3u8 {
+        let request = initiator_send.seal(b"aad", &[i; 8]).unwrap();
+        assert_eq!(responder_recv.open(b"aad", &request).unwrap(), [i; 8]);
+        let response = responder_send.seal(b"aad", &[i; 8]).unwrap();
+        assert_eq!(initiator_recv.open(b"aad", &response).unwrap(), [i; 8]);
+
+        // The directions use different keys.
+        assert_ne!(request, response);
+    }
+}
+
+#[test]
+fn test_aad_prefix() {
+    let new_hpke = |prefix: &[u8]| base_hpke().with_aad_prefix(prefix.to_vec());
+    let hpke_a = new_hpke(b"protocol A");
+    let hpke_b = new_hpke(b"protocol B");
+    let (sk_r, pk_r) = hpke_a.generate_key_pair().into_keys();
+
+    let (enc, ctxt) = hpke_a
+        .seal(&pk_r, b"info", b"aad", b"plain text", None, None, None)
+        .unwrap();
+    assert_eq!(
+        hpke_a
+            .open(&enc, &sk_r, b"info", b"aad", &ctxt, None, None, None)
+            .unwrap(),
+        b"plain text"
+    );
+    assert_eq!(
+        hpke_b.open(&enc, &sk_r, b"info", b"aad", &ctxt, None, None, None),
+        Err(HPKEError::OpenError)
+    );
+
+    // The length-prefixed prefix is prepended to the caller's aad.
+    let hpke = new_hpke(b"");
+    let mut context = hpke
+        .setup_receiver(&enc, &sk_r, b"info", None, None, None)
+        .unwrap();
+    assert_eq!(
+        context
+            .open(b"\0\0\0\0\0\0\0\x0aprotocol Aaad", &ctxt)
+            .unwrap(),
+        b"plain text"
+    );
+
+    // Overlapping prefixes don't open each other's ciphertexts.
+    let hpke_ab = new_hpke(b"ab");
+    let hpke_a = new_hpke(b"a");
+    let (enc, ctxt) = hpke_ab
+        .seal(&pk_r, b"info", b"", b"plain text", None, None, None)
+        .unwrap();
+    assert_eq!(
+        hpke_a.open(&enc, &sk_r, b"info", b"b", &ctxt, None, None, None),
+        Err(HPKEError::OpenError)
+    );
+    let mut context = hpke_a
+        .setup_receiver(&enc, &sk_r, b"info", None, None, None)
+        .unwrap();
+    assert_eq!(
+        context.open_vectored(&[b"b"], &ctxt),
+        Err(HPKEError::OpenError)
+    );
+}
+
+#[test]
+fn test_public_key_id() {
+    let hpke = base_hpke();
+    let other_suite = Hpke::new(
+        HpkeMode::Base,
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::ChaCha20Poly1305,
+    );
+    let (_, pk_1) = hpke.generate_key_pair().into_keys();
+    let (_, pk_2) = hpke.generate_key_pair().into_keys();
+
+    let id = hpke.public_key_id(&pk_1);
+    assert_eq!(id, hpke.public_key_id(&pk_1.clone()));
+    assert_ne!(id, hpke.public_key_id(&pk_2));
+    assert_ne!(id, other_suite.public_key_id(&pk_1));
+}
+
+#[test]
+fn test_export_bound() {
+    let hpke = base_hpke();
+    let (sender_context, receiver_context) = base_pair(&hpke, b"info");
+
+    let a = sender_context
+        .export_bound(b"transcript a", b"exporter context", 32)
+        .unwrap();
+    let b = sender_context
+        .export_bound(b"transcript b", b"exporter context", 32)
+        .unwrap();
+    assert_ne!(a, b);
+    assert_ne!(a, sender_context.export(b"exporter context", 32));
+    assert_eq!(
+        a,
+        receiver_context
+            .export_bound(b"transcript a", b"exporter context", 32)
+            .unwrap()
+    );
+
+    // The transcript is length prefixed.
+    assert_ne!(
+        sender_context.export_bound(b"ab", b"c", 32).unwrap(),
+        sender_context.export_bound(b"a", b"bc", 32).unwrap()
+    );
+
+    assert_eq!(
+        sender_context.export_bound(b"transcript a", b"exporter context", 255 * 32 + 1),
+        Err(HPKEError::InvalidInput)
+    );
+}
+
+#[test]
+fn test_send_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Hpke>();
+    assert_send_sync::<Context>();
+    assert_send_sync::<HPKEKeyPair>();
+    assert_send_sync::<HPKEError>();
+
+    let hpke = std::sync::Arc::new(base_hpke());
+    let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+    let threads: Vec<_> = (0..4u8)
+        .map(|i| {
+            let hpke = hpke.clone();
+            let pk_r = pk_r.clone();
+            std::thread::spawn(move || {
+                let (enc, mut context) =
+                    hpke.setup_sender(&pk_r, b"info", None, None, None).unwrap();
+                (enc, context.seal(b"aad", &[i; 8]).unwrap())
+            })
+        })
+        .collect();
+    for (i, thread) in threads.into_iter().enumerate() {
+        let (enc, ctxt) = thread.join().unwrap();
+        let mut context = hpke
+            .setup_receiver(&enc, &sk_r, b"info", None, None, None)
+            .unwrap();
+        assert_eq!(context.open(b"aad", &ctxt).unwrap(), [i as u8; 8]);
+    }
+}
+
+#[test]
+fn test_rewrap() {
+    let hpke = base_hpke();
+    let (sk_relay, pk_relay) = hpke.generate_key_pair().into_keys();
+    let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+
+    let (enc, ctxt) = hpke
+        .seal(&pk_relay, b"info", b"aad", b"plain text", None, None, None)
+        .unwrap();
+    let (new_enc, new_ctxt) = hpke
+        .rewrap(&enc, &sk_relay, &pk_r, b"info", b"aad", &ctxt, None, None)
+        .unwrap();
+    assert_ne!(enc, new_enc);
+    assert_eq!(
+        hpke.open(&new_enc, &sk_r, b"info", b"aad", &new_ctxt, None, None, None)
+            .unwrap(),
+        b"plain text"
+    );
+
+    // The relay can't open for the wrong key.
+    assert_eq!(
+        hpke.rewrap(&enc, &sk_r, &pk_r, b"info", b"aad", &ctxt, None, None),
+        Err(HPKEError::OpenError)
+    );
+
+    let auth_hpke = Hpke::new(
+        HpkeMode::Auth,
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::AesGcm128,
+    );
+    assert_eq!(
+        auth_hpke.rewrap(&enc, &sk_relay, &pk_r, b"info", b"aad", &ctxt, None, None),
+        Err(HPKEError::InvalidConfig)
+    );
+}
+
+#[test]
+fn test_max_seal_output() {
+    let hpke = base_hpke().with_limits(HpkeLimits {
+        max_seal_output: 32,
+        ..HpkeLimits::default()
+    });
+    let (mut sender_context, mut receiver_context) = base_pair(&hpke, b"info");
+
+    // The output would be 17 + 16 bytes.
+    assert_eq!(
+        sender_context.seal(b"aad", &[0u8; 17]),
+        Err(HPKEError::InvalidInput)
+    );
+    assert_eq!(
+        sender_context.seal_detached(b"aad", &[0u8; 17]),
+        Err(HPKEError::InvalidInput)
+    );
+    assert_eq!(
+        sender_context.seal(b"aad", &vec![0u8; 1 << 20]),
+        Err(HPKEError::InvalidInput)
+    );
+    assert_eq!(sender_context.sequence_number(), 0);
+
+    // A 16 byte plain text fits exactly.
+    let ctxt = sender_context.seal(b"aad", &[0u8; 16]).unwrap();
+    assert_eq!(ctxt.len(), 32);
+    assert_eq!(receiver_context.open(b"aad", &ctxt).unwrap(), [0u8; 16]);
+}
+
+#[test]
+fn test_psk_type() {
+    assert_eq!(
+        Psk::new(vec![], vec![0x42; 32]).unwrap_err(),
+        HPKEError::InconsistentPsk
+    );
+    assert_eq!(
+        Psk::new(b"psk id".to_vec(), vec![]).unwrap_err(),
+        HPKEError::InconsistentPsk
+    );
+    assert_eq!(
+        Psk::new(b"psk id".to_vec(), vec![0x42; 31]).unwrap_err(),
+        HPKEError::InsecurePsk
+    );
+    let psk = Psk::new(b"psk id".to_vec(), vec![0x42; 32]).unwrap();
+    assert_eq!(psk.id(), b"psk id");
+    assert!(!format!("{:?}", psk).contains("66, 66"));
+
+    let hpke = Hpke::new(
+        HpkeMode::Psk,
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::AesGcm128,
+    );
+    let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+    let (enc, mut sender_context) = hpke.setup_sender_psk(&pk_r, b"info", &psk, None).unwrap();
+    let mut receiver_context = hpke
+        .setup_receiver_psk(&enc, &sk_r, b"info", &psk, None)
+        .unwrap();
+    let ctxt = sender_context.seal(b"aad", b"plain text").unwrap();
+    assert_eq!(receiver_context.open(b"aad", &ctxt).unwrap(), b"plain text");
+
+    // The PSK is the same as passing its parts.
+    let mut other_receiver_context = hpke
+        .setup_receiver(
+            &enc,
+            &sk_r,
+            b"info",
+            Some(&[0x42; 32]),
+            Some(b"psk id"),
+            None,
+        )
+        .unwrap();
+    assert_eq!(
+        other_receiver_context.open(b"aad", &ctxt).unwrap(),
+        b"plain text"
+    );
+
+    // A PSK in the base mode is still an error.
+    let base_hpke = base_hpke();
+    assert_eq!(
+        base_hpke
+            .setup_sender_psk(&pk_r, b"info", &psk, None)
+            .unwrap_err(),
+        HPKEError::PskMismatch {
+            expected_psk: false,
+            got_psk: true
+        }
+    );
+}
+
+#[test]
+fn test_random_nonce_context() {
+    let hpke = Hpke::new(
+        HpkeMode::Base,
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::ChaCha20Poly1305,
+    );
+    let (sender_context, receiver_context) = base_pair(&hpke, b"info");
+    let sender_context = RandomNonceContext::new(sender_context);
+    let receiver_context = RandomNonceContext::new(receiver_context);
+
+    let ctxt_1 = sender_context.seal(b"aad", b"plain text").unwrap();
+    let ctxt_2 = sender_context.seal(b"aad", b"plain text").unwrap();
+    assert_eq!(ctxt_1.len(), 12 + b"plain text".len() + 16);
+    assert_ne!(ctxt_1, ctxt_2);
+
+    // Messages can be opened in any order.
+    assert_eq!(
+        receiver_context.open(b"aad", &ctxt_2).unwrap(),
+        b"plain text"
+    );
+    assert_eq!(
+        receiver_context.open(b"aad", &ctxt_1).unwrap(),
+        b"plain text"
+    );
+    assert_eq!(
+        receiver_context.open(b"aad", &ctxt_1[..11]),
+        Err(HPKEError::OpenError)
+    );
+    assert_eq!(receiver_context.context().sequence_number(), 0);
+}
+
+#[test]
+fn test_exporter_secret_len() {
+    for &(kdf_mode, nh) in &[
+        (HpkeKdfMode::HkdfSha256, 32),
+        (HpkeKdfMode::HkdfSha384, 48),
+        (HpkeKdfMode::HkdfSha512, 64),
+    ] {
+        let hpke = Hpke::new(
+            HpkeMode::Base,
+            HpkeKemMode::DhKem25519,
+            kdf_mode,
+            HpkeAeadMode::AesGcm128,
+        );
+        let (_sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+        let (_enc, context) = hpke.setup_sender(&pk_r, b"info", None, None, None).unwrap();
+        assert_eq!(hpke.exporter_secret_len(), nh);
+        assert_eq!(context.exporter_secret().len(), hpke.exporter_secret_len());
+    }
+}
+
+#[test]
+fn test_wrap_key() {
+    let hpke = Hpke::new(
+        HpkeMode::Base,
+        HpkeKemMode::DhKemP256,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::AesGcm256,
+    );
+    let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+    let dek = hpke::random_bytes(32);
+
+    let (enc, wrapped) = hpke
+        .wrap_key(&pk_r, b"kms key id", &dek, None, None, None)
+        .unwrap();
+    assert_eq!(wrapped.len(), 32 + 16);
+    let unwrapped: Zeroizing<Vec<u8>> = hpke
+        .unwrap_key(&enc, &sk_r, b"kms key id", &wrapped, None, None, None)
+        .unwrap();
+    assert_eq!(&unwrapped[..], &dek[..]);
+
+    // Wrapping the same key twice uses fresh contexts.
+    let (enc_2, wrapped_2) = hpke
+        .wrap_key(&pk_r, b"kms key id", &dek, None, None, None)
+        .unwrap();
+    assert_ne!(enc, enc_2);
+    assert_ne!(wrapped, wrapped_2);
+
+    assert_eq!(
+        hpke.unwrap_key(&enc, &sk_r, b"other key id", &wrapped, None, None, None)
+            .map(|dek| dek.len()),
+        Err(HPKEError::OpenError)
+    );
+}
+
+#[test]
+fn test_assume_valid_psk() {
+    let new_hpke = || {
+        Hpke::new(
+            HpkeMode::Psk,
+            HpkeKemMode::DhKem25519,
+            HpkeKdfMode::HkdfSha256,
+            HpkeAeadMode::AesGcm128,
+        )
+    };
+    let hpke = new_hpke();
+    let trusting_hpke = new_hpke().assume_valid_psk();
+    let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+    let psk = [0x42u8; 32];
+
+    // The PSK id is missing.
+    assert_eq!(
+        hpke.setup_sender(&pk_r, b"info", Some(&psk), None, None)
+            .unwrap_err(),
+        HPKEError::InconsistentPsk
+    );
+    let (enc, mut sender_context) = trusting_hpke
+        .setup_sender(&pk_r, b"info", Some(&psk), None, None)
+        .unwrap();
+    let mut receiver_context = trusting_hpke
+        .setup_receiver(&enc, &sk_r, b"info", Some(&psk), None, None)
+        .unwrap();
+    let ctxt = sender_context.seal(b"aad", b"plain text").unwrap();
+    assert_eq!(receiver_context.open(b"aad", &ctxt).unwrap(), b"plain text");
+}
+
+#[test]
+fn test_import_shared_secret() {
+    let hpke = Hpke::new(
+        HpkeMode::Psk,
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::AesGcm128,
+    );
+    let shared_secret = hpke::random_bytes(32);
+    let psk = [0x42u8; 32];
+
+    let mut sender_context = hpke
+        .import_shared_secret(&shared_secret, b"info", Some(&psk), Some(b"psk id"))
+        .unwrap();
+    let mut receiver_context = hpke
+        .import_shared_secret(&shared_secret, b"info", Some(&psk), Some(b"psk id"))
+        .unwrap();
+    for i in 0..3u8 {
+        let ctxt = sender_context.seal(b"aad", &[i; 4]).unwrap();
+        assert_eq!(receiver_context.open(b"aad", &ctxt).unwrap(), [i; 4]);
+    }
+
+    // The result is the same as for a KEM shared secret.
+    let (_sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+    let (_enc, zz, context) = hpke
+        .setup_sender_with_shared_secret(&pk_r, b"info", Some(&psk), Some(b"psk id"), None)
+        .unwrap();
+    let imported_context = hpke
+        .import_shared_secret(&zz, b"info", Some(&psk), Some(b"psk id"))
+        .unwrap();
+    assert_eq!(
+        context.export(b"context", 32),
+        imported_context.export(b"context", 32)
+    );
+
+    // The PSK inputs are checked.
+    assert_eq!(
+        hpke.import_shared_secret(&shared_secret, b"info", None, None)
+            .unwrap_err(),
+        HPKEError::PskMismatch {
+            expected_psk: true,
+            got_psk: false
+        }
+    );
+}
+
+#[test]
+fn test_implicit_aad() {
+    let hpke = base_hpke();
+    let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+    let (enc, mut sender_context) = hpke.setup_sender(&pk_r, b"info", None, None, None).unwrap();
+    let mut receiver_context = hpke
+        .setup_receiver(&enc, &sk_r, b"info", None, None, None)
+        .unwrap();
+
+    // The receiver knows the connection id from its own state.
+    let connection_id = b"connection 42";
+    let header = b"header";
+    let ctxt = sender_context
+        .seal_implicit_aad(header, connection_id, b"plain text")
+        .unwrap();
+
+    let mut other_receiver_context = hpke
+        .setup_receiver(&enc, &sk_r, b"info", None, None, None)
+        .unwrap();
+    assert_eq!(
+        other_receiver_context.open_implicit_aad(header, b"connection 43", &ctxt),
+        Err(HPKEError::OpenError)
+    );
+    assert_eq!(
+        other_receiver_context.open_implicit_aad(b"headerconnection 42", b"", &ctxt),
+        Err(HPKEError::OpenError)
+    );
+    assert_eq!(
+        receiver_context
+            .open_implicit_aad(header, connection_id, &ctxt)
+            .unwrap(),
+        b"plain text"
+    );
+}
+
+#[test]
+fn test_setup_with_negotiation() {
+    let hpke = base_hpke();
+    let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+    let offer_accept =
+        b"offer: 0x0020/0x0001/0x0001, 0x0010/0x0001/0x0001; accept: 0x0020/0x0001/0x0001";
+    let tampered = b"offer: 0x0020/0x0001/0x0001; accept: 0x0020/0x0001/0x0001";
+
+    let (enc, mut sender_context) = hpke
+        .setup_sender_with_negotiation(&pk_r, offer_accept, b"info", None, None, None)
+        .unwrap();
+    let ctxt = sender_context.seal(b"aad", b"plain text").unwrap();
+
+    let mut receiver_context = hpke
+        .setup_receiver_with_negotiation(&enc, &sk_r, offer_accept, b"info", None, None, None)
+        .unwrap();
+    assert_eq!(receiver_context.open(b"aad", &ctxt).unwrap(), b"plain text");
+
+    let mut tampered_context = hpke
+        .setup_receiver_with_negotiation(&enc, &sk_r, tampered, b"info", None, None, None)
+        .unwrap();
+    assert_eq!(
+        tampered_context.open(b"aad", &ctxt),
+        Err(HPKEError::OpenError)
+    );
+    let mut unbound_context = hpke
+        .setup_receiver(&enc, &sk_r, b"info", None, None, None)
+        .unwrap();
+    assert_eq!(
+        unbound_context.open(b"aad", &ctxt),
+        Err(HPKEError::OpenError)
+    );
+}
+
+#[test]
+fn test_generate_key_pair_for_kem() {
+    for &kem_mode in &[HpkeKemMode::DhKem25519, HpkeKemMode::DhKemP256] {
+        let (sk_r, pk_r) = hpke::generate_key_pair(kem_mode).unwrap().into_keys();
+        let hpke = Hpke::new(
+            HpkeMode::Base,
+            kem_mode,
+            HpkeKdfMode::HkdfSha384,
+            HpkeAeadMode::ChaCha20Poly1305,
+        );
+        let (enc, ctxt) = hpke
+            .seal(&pk_r, b"info", b"aad", b"plain text", None, None, None)
+            .unwrap();
+        assert_eq!(
+            hpke.open(&enc, &sk_r, b"info", b"aad", &ctxt, None, None, None)
+                .unwrap(),
+            b"plain text"
+        );
+
+        let (sk_r, pk_r) = HpkeKem::try_new(kem_mode)
+            .unwrap()
+            .generate_key_pair()
+            .into_keys();
+        let (enc, ctxt) = hpke
+            .seal(&pk_r, b"info", b"aad", b"plain text", None, None, None)
+            .unwrap();
+        assert_eq!(
+            hpke.open(&enc, &sk_r, b"info", b"aad", &ctxt, None, None, None)
+                .unwrap(),
+            b"plain text"
+        );
+    }
+
+    for &kem_mode in &[
+        HpkeKemMode::DhKemP384,
+        HpkeKemMode::DhKemP521,
+        HpkeKemMode::DhKem448,
+    ] {
+        assert_eq!(
+            hpke::generate_key_pair(kem_mode).unwrap_err(),
+            HPKEError::UnsupportedSuite
+        );
+        assert!(HpkeKem::try_new(kem_mode).is_err());
+    }
+}
+
+#[test]
+fn test_auth_key_lengths() {
+    let hpke = Hpke::new(
+        HpkeMode::Auth,
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::AesGcm128,
+    );
+    let p256_hpke = Hpke::new(
+        HpkeMode::Auth,
+        HpkeKemMode::DhKemP256,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::AesGcm128,
+    );
+    let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+    let (sk_s, pk_s) = hpke.generate_key_pair().into_keys();
+    let (p256_sk_s, p256_pk_s) = p256_hpke.generate_key_pair().into_keys();
+
+    // A X448 sized private key.
+    let x448_sk_s = HPKEPrivateKey::new(vec![0x42; 56]);
+    assert_eq!(
+        hpke.setup_sender(&pk_r, b"info", None, None, Some(&x448_sk_s))
+            .unwrap_err(),
+        HPKEError::InvalidInput
+    );
+
+    let (enc, _sender_context) = hpke
+        .setup_sender(&pk_r, b"info", None, None, Some(&sk_s))
+        .unwrap();
+    assert_eq!(
+        hpke.setup_receiver(&enc, &sk_r, b"info", None, None, Some(&p256_pk_s))
+            .unwrap_err(),
+        HPKEError::InvalidInput
+    );
+    assert!(hpke
+        .setup_receiver(&enc, &sk_r, b"info", None, None, Some(&pk_s))
+        .is_ok());
+
+    // A X25519 public key for a P256 configuration.
+    let (p256_sk_r, p256_pk_r) = p256_hpke.generate_key_pair().into_keys();
+    let (p256_enc, _) = p256_hpke
+        .setup_sender(&p256_pk_r, b"info", None, None, Some(&p256_sk_s))
+        .unwrap();
+    assert_eq!(
+        p256_hpke
+            .setup_receiver(&p256_enc, &p256_sk_r, b"info", None, None, Some(&pk_s))
+            .unwrap_err(),
+        HPKEError::InvalidInput
+    );
+}
+
+#[test]
+fn test_binder() {
+    let hpke = Hpke::new(
+        HpkeMode::Base,
+        HpkeKemMode::DhKemP256,
+        HpkeKdfMode::HkdfSha384,
+        HpkeAeadMode::AesGcm256,
+    );
+    let (sender_context, receiver_context) = base_pair(&hpke, b"info");
+
+    let binder = sender_context.binder(b"transcript mac");
+    assert_eq!(binder.len(), 48);
+    assert_eq!(binder, receiver_context.binder(b"transcript mac"));
+    assert_ne!(binder, sender_context.binder(b"other label"));
+    assert_ne!(binder, sender_context.export(b"transcript mac", 48));
+}
+
+#[test]
+fn test_seal_multi() {
+    let hpke = Hpke::new(
+        HpkeMode::Base,
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::ChaCha20Poly1305,
+    );
+    let (mut sender_context, mut receiver_context) = base_pair(&hpke, b"info");
+
+    let records: [&[u8]; 3] = [b"", b"a", &[0x42; 300]];
+    let ctxt = sender_context.seal_multi(b"aad", &records).unwrap();
+    assert_eq!(sender_context.sequence_number(), 1);
+    let parts = receiver_context.open_multi(b"aad", &ctxt).unwrap();
+    assert_eq!(parts, records);
+
+    // A single record that isn't framed.
+    let ctxt = sender_context.seal(b"aad", b"unframed").unwrap();
+    assert_eq!(
+        receiver_context.open_multi(b"aad", &ctxt).unwrap_err(),
+        HPKEError::InvalidInput
+    );
+}
+
+#[test]
+fn test_context_from_parts_nonce_len() {
+    let hpke = base_hpke();
+    let key = vec![0u8; 16];
+    let exporter_secret = vec![0u8; 32];
+    assert!(hpke
+        .context_from_parts(key.clone(), vec![0u8; 12], exporter_secret.clone())
+        .is_ok());
+    for nonce_len in &[0usize, 11, 13, 24] {
+        assert_eq!(
+            hpke.context_from_parts(key.clone(), vec![0u8; *nonce_len], exporter_secret.clone())
+                .unwrap_err(),
+            HPKEError::InvalidConfig
+        );
+    }
+}
+
+#[test]
+fn test_ratchet_chain() {
+    let hpke = Hpke::new(
+        HpkeMode::Base,
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::ChaCha20Poly1305,
+    );
+    let (sender_context, receiver_context) = base_pair(&hpke, b"info");
+
+    let sender_keys = sender_context.ratchet_chain(5);
+    assert_eq!(sender_keys.len(), 5);
+    assert_eq!(sender_keys, receiver_context.ratchet_chain(5));
+    assert!(sender_keys.iter().all(|key| key.len() == 32));
+    for (i, key) in sender_keys.iter().enumerate() {
+        assert!(sender_keys[i + 1..].iter().all(|other| other != key));
+    }
+
+    // Longer chains extend shorter ones.
+    assert_eq!(sender_context.ratchet_chain(2), sender_keys[..2]);
+    assert!(sender_context.ratchet_chain(0).is_empty());
+}
+
+#[test]
+fn test_seal_binding_enc() {
+    let hpke = Hpke::new(
+        HpkeMode::Base,
+        HpkeKemMode::DhKemP256,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::AesGcm128,
+    );
+    let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+    let (enc_1, ct_1) = hpke
+        .seal_binding_enc(&pk_r, b"info", b"aad", b"first", None, None, None)
+        .unwrap();
+    let (enc_2, ct_2) = hpke
+        .seal_binding_enc(&pk_r, b"info", b"aad", b"second", None, None, None)
+        .unwrap();
+
+    let pt = hpke
+        .open_binding_enc(&enc_1, &sk_r, b"info", b"aad", &ct_1, None, None, None)
+        .unwrap();
+    assert_eq!(pt, b"first");
+
+    // The encapsulated secret is part of the aad.
+    let aad = [&enc_2[..], b"aad"].concat();
+    let pt = hpke
+        .open(&enc_2, &sk_r, b"info", &aad, &ct_2, None, None, None)
+        .unwrap();
+    assert_eq!(pt, b"second");
+
+    // Swapping the encapsulated secrets fails.
+    assert_eq!(
+        hpke.open_binding_enc(&enc_2, &sk_r, b"info", b"aad", &ct_1, None, None, None)
+            .unwrap_err(),
+        HPKEError::OpenError
+    );
+    assert_eq!(
+        hpke.open_binding_enc(&enc_1, &sk_r, b"info", b"aad", &ct_2, None, None, None)
+            .unwrap_err(),
+        HPKEError::OpenError
+    );
+}
+
+#[test]
+fn test_nonce_for_seq() {
+    let hpke = base_hpke();
+    let (_sender_context, receiver_context) = base_pair(&hpke, b"info");
+
+    let base_nonce = receiver_context.nonce();
+    assert_eq!(receiver_context.nonce_for_seq(0).unwrap(), base_nonce);
+    let mut expected = base_nonce.to_vec();
+    expected[11] ^= 1;
+    assert_eq!(receiver_context.nonce_for_seq(1).unwrap(), expected);
+    let mut expected = base_nonce.to_vec();
+    expected[8..].iter_mut().for_each(|b| *b ^= 0xff);
+    assert_eq!(
+        receiver_context.nonce_for_seq(u32::MAX as u128).unwrap(),
+        expected
+    );
+    assert_eq!(
+        receiver_context
+            .nonce_for_seq(u32::MAX as u128 + 1)
+            .unwrap_err(),
+        HPKEError::InvalidInput
+    );
+    assert_eq!(receiver_context.sequence_number(), 0);
+}
+
+#[test]
+fn test_seal_committing() {
+    let hpke = base_hpke();
+    let (mut sender_context, mut receiver_context) = base_pair(&hpke, b"info");
+    let ctxt = sender_context.seal_committing(b"aad", b"message").unwrap();
+    assert_eq!(ctxt.len(), 32 + 7 + 16);
+    assert_eq!(
+        receiver_context.open_committing(b"aad", &ctxt).unwrap(),
+        b"message"
+    );
+
+    // Two contexts with different keys.
+    let nonce = vec![7u8; 12];
+    let exporter_secret = vec![0u8; 32];
+    let part_context = |key: u8| {
+        hpke.context_from_parts(vec![key; 16], nonce.clone(), exporter_secret.clone())
+            .unwrap()
+    };
+    let committed_a = part_context(1).seal_committing(b"aad", b"message").unwrap();
+    let committed_b = part_context(2).seal_committing(b"aad", b"message").unwrap();
+
+    // A ciphertext that opens under key b, but carries the commitment to key a,
+    // as it would if it had been crafted to open under both keys.
+    let mut crafted = committed_a[..32].to_vec();
+    crafted.extend_from_slice(&committed_b[32..]);
+    assert_eq!(
+        part_context(2).open(b"aad", &crafted[32..]).unwrap(),
+        b"message"
+    );
+    assert_eq!(
+        part_context(2)
+            .open_committing(b"aad", &crafted)
+            .unwrap_err(),
+        HPKEError::OpenError
+    );
+    assert_eq!(
+        part_context(1)
+            .open_committing(b"aad", &crafted)
+            .unwrap_err(),
+        HPKEError::OpenError
+    );
+    assert_eq!(
+        part_context(2)
+            .open_committing(b"aad", &crafted[..31])
+            .unwrap_err(),
+        HPKEError::OpenError
+    );
+    assert_eq!(
+        part_context(2)
+            .open_committing(b"aad", &committed_b)
+            .unwrap(),
+        b"message"
+    );
+}
+
+#[test]
+fn test_context_metadata() {
+    let hpke = Hpke::new(
+        HpkeMode::Base,
+        HpkeKemMode::DhKemP256,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::AesGcm256,
+    );
+    let (mut sender_context, receiver_context) = base_pair(&hpke, b"info");
+
+    let metadata = sender_context.metadata();
+    assert_eq!(metadata.mode, HpkeMode::Base);
+    assert_eq!(metadata.kem_id, HpkeKemMode::DhKemP256);
+    assert_eq!(metadata.kdf_id, HpkeKdfMode::HkdfSha256);
+    assert_eq!(metadata.aead_id, HpkeAeadMode::AesGcm256);
+    assert_eq!(metadata.sequence_number, 0);
+    assert_eq!(metadata, receiver_context.metadata());
+
+    // The metadata doesn't contain any key material.
+    let metadata_str = format!("{:?}", metadata);
+    for secret in &[
+        sender_context.key(),
+        sender_context.nonce(),
+        sender_context.exporter_secret(),
+    ] {
+        assert!(!metadata_str.contains(&format!("{:?}", secret)));
+        assert!(!metadata_str.contains(&format!("{:?}", &secret[..8])));
+        assert!(secret
+            .windows(8)
+            .all(|window| window != metadata.fingerprint));
+    }
+
+    sender_context.seal(b"aad", b"message").unwrap();
+    let metadata = sender_context.metadata();
+    assert_eq!(metadata.sequence_number, 1);
+    assert_eq!(
+        metadata.fingerprint,
+        receiver_context.metadata().fingerprint
+    );
+}
+
+#[test]
+fn test_mac() {
+    let hpke = Hpke::new(
+        HpkeMode::Base,
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha512,
+        HpkeAeadMode::ChaCha20Poly1305,
+    );
+    let (sender_context, receiver_context) = base_pair(&hpke, b"info");
+
+    let sender_key = sender_context.export_mac_key(b"legacy mac");
+    let receiver_key = receiver_context.export_mac_key(b"legacy mac");
+    assert_eq!(sender_key.len(), 64);
+    assert_eq!(sender_key, receiver_key);
+    assert_ne!(sender_key, sender_context.export_mac_key(b"other mac"));
+
+    let tag = sender_context.compute_mac(&sender_key, b"data");
+    assert_eq!(tag.len(), 64);
+    assert!(receiver_context.verify_mac(&receiver_key, b"data", &tag));
+
+    // Wrong tags, data, and keys fail.
+    let mut wrong_tag = tag.clone();
+    wrong_tag[63] ^= 1;
+    assert!(!receiver_context.verify_mac(&receiver_key, b"data", &wrong_tag));
+    assert!(!receiver_context.verify_mac(&receiver_key, b"data", &tag[..32]));
+    assert!(!receiver_context.verify_mac(&receiver_key, b"other data", &tag));
+    assert!(!receiver_context.verify_mac(&receiver_key[1..], b"data", &tag));
+}
+
+#[test]
+fn test_external_seq() {
+    let hpke = base_hpke();
+    let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+    let (enc, mut sender_context) = hpke.setup_sender(&pk_r, b"info", None, None, None).unwrap();
+    let external_sender = hpke
+        .setup_receiver(&enc, &sk_r, b"info", None, None, None)
+        .unwrap()
+        .with_external_counter();
+    let external_receiver = hpke
+        .setup_receiver(&enc, &sk_r, b"info", None, None, None)
+        .unwrap()
+        .with_external_counter();
+
+    for seq in 0u128..5 {
+        let pt = format!("message {}", seq);
+        let ctxt = sender_context.seal(b"aad", pt.as_bytes()).unwrap();
+        assert_eq!(
+            external_sender
+                .seal_external_seq(seq, b"aad", pt.as_bytes())
+                .unwrap(),
+            ctxt
+        );
+        assert_eq!(
+            external_receiver
+                .open_external_seq(seq, b"aad", &ctxt)
+                .unwrap(),
+            pt.as_bytes()
+        );
+    }
+    assert_eq!(external_sender.sequence_number(), 0);
+
+    // The internal counter is disabled.
+    let mut external_sender = external_sender;
+    assert_eq!(
+        external_sender.seal(b"aad", b"message").unwrap_err(),
+        HPKEError::InvalidConfig
+    );
+    assert_eq!(
+        external_sender
+            .seal_external_seq(u32::MAX as u128 + 1, b"aad", b"message")
+            .unwrap_err(),
+        HPKEError::InvalidInput
+    );
+}
+
+#[test]
+fn test_named_setup() {
+    let psk = [0x51u8; 32];
+    let psk_id = b"psk id";
+    let new_hpke = |mode| {
+        Hpke::new(
+            mode,
+            HpkeKemMode::DhKem25519,
+            HpkeKdfMode::HkdfSha256,
+            HpkeAeadMode::AesGcm128,
+        )
+    };
+    let base = new_hpke(HpkeMode::Base);
+    let psk_hpke = new_hpke(HpkeMode::Psk);
+    let auth = new_hpke(HpkeMode::Auth);
+    let auth_psk = new_hpke(HpkeMode::AuthPsk);
+    let (sk_r, pk_r) = base.generate_key_pair().into_keys();
+    let (sk_s, pk_s) = base.generate_key_pair().into_keys();
+
+    // Each constructor works in its own mode.
+    let (enc, mut sender) = base.setup_base_sender(&pk_r, b"info").unwrap();
+    let mut receiver = base.setup_base_receiver(&enc, &sk_r, b"info").unwrap();
+    let ctxt = sender.seal(b"aad", b"base").unwrap();
+    assert_eq!(receiver.open(b"aad", &ctxt).unwrap(), b"base");
+
+    let (enc, mut sender) = psk_hpke
+        .setup_psk_sender(&pk_r, b"info", &psk, psk_id)
+        .unwrap();
+    let mut receiver = psk_hpke
+        .setup_psk_receiver(&enc, &sk_r, b"info", &psk, psk_id)
+        .unwrap();
+    let ctxt = sender.seal(b"aad", b"psk").unwrap();
+    assert_eq!(receiver.open(b"aad", &ctxt).unwrap(), b"psk");
+
+    let (enc, mut sender) = auth.setup_auth_sender(&pk_r, b"info", &sk_s).unwrap();
+    let mut receiver = auth
+        .setup_auth_receiver(&enc, &sk_r, b"info", &pk_s)
+        .unwrap();
+    let ctxt = sender.seal(b"aad", b"auth").unwrap();
+    assert_eq!(receiver.open(b"aad", &ctxt).unwrap(), b"auth");
+
+    let (enc, mut sender) = auth_psk
+        .setup_auth_psk_sender(&pk_r, b"info", &psk, psk_id, &sk_s)
+        .unwrap();
+    let mut receiver = auth_psk
+        .setup_auth_psk_receiver(&enc, &sk_r, b"info", &psk, psk_id, &pk_s)
+        .unwrap();
+    let ctxt = sender.seal(b"aad", b"auth psk").unwrap();
+    assert_eq!(receiver.open(b"aad", &ctxt).unwrap(), b"auth psk");
+
+    // The wrong mode is rejected.
+    for hpke in &[&psk_hpke, &auth, &auth_psk] {
+        assert_eq!(
+            hpke.setup_base_sender(&pk_r, b"info").unwrap_err(),
+            HPKEError::InvalidConfig
+        );
+        assert_eq!(
+            hpke.setup_base_receiver(&enc, &sk_r, b"info").unwrap_err(),
+            HPKEError::InvalidConfig
+        );
+    }
+    for hpke in &[&base, &auth, &auth_psk] {
+        assert_eq!(
+            hpke.setup_psk_sender(&pk_r, b"info", &psk, psk_id)
+                .unwrap_err(),
+            HPKEError::InvalidConfig
+        );
+        assert_eq!(
+            hpke.setup_psk_receiver(&enc, &sk_r, b"info", &psk, psk_id)
+                .unwrap_err(),
+            HPKEError::InvalidConfig
+        );
+    }
+    for hpke in &[&base, &psk_hpke, &auth_psk] {
+        assert_eq!(
+            hpke.setup_auth_sender(&pk_r, b"info", &sk_s).unwrap_err(),
+            HPKEError::InvalidConfig
+        );
+        assert_eq!(
+            hpke.setup_auth_receiver(&enc, &sk_r, b"info", &pk_s)
+                .unwrap_err(),
+            HPKEError::InvalidConfig
+        );
+    }
+    for hpke in &[&base, &psk_hpke, &auth] {
+        assert_eq!(
+            hpke.setup_auth_psk_sender(&pk_r, b"info", &psk, psk_id, &sk_s)
+                .unwrap_err(),
+            HPKEError::InvalidConfig
+        );
+        assert_eq!(
+            hpke.setup_auth_psk_receiver(&enc, &sk_r, b"info", &psk, psk_id, &pk_s)
+                .unwrap_err(),
+            HPKEError::InvalidConfig
+        );
+    }
+}
+
+#[test]
+fn test_max_open_input() {
+    let hpke = base_hpke().with_limits(HpkeLimits {
+        max_open_input: 32,
+        ..HpkeLimits::default()
+    });
+    let (sk_r, pk_r) = hpke.generate_key_pair().into_keys();
+    let (enc, mut sender_context) = hpke.setup_sender(&pk_r, b"info", None, None, None).unwrap();
+    let mut receiver_context = hpke
+        .setup_receiver(&enc, &sk_r, b"info", None, None, None)
+        .unwrap();
+
+    let ctxt = sender_context.seal(b"aad", &[0u8; 16]).unwrap();
+    assert_eq!(receiver_context.open(b"aad", &ctxt).unwrap(), [0u8; 16]);
+
+    // Garbage that fails to open with an `OpenError` in the AEAD is rejected
+    // with an `InvalidInput` error before the AEAD is invoked.
+    assert_eq!(
+        receiver_context.open(b"aad", &[0u8; 32]).unwrap_err(),
+        HPKEError::OpenError
+    );
+    assert_eq!(
+        receiver_context.open(b"aad", &[0u8; 33]).unwrap_err(),
+        HPKEError::InvalidInput
+    );
+
+    // The detached tag counts towards the input length.
+    assert_eq!(
+        receiver_context
+            .open_detached(b"aad", &[0u8; 16], &[0u8; 16])
+            .unwrap_err(),
+        HPKEError::OpenError
+    );
+    assert_eq!(
+        receiver_context
+            .open_detached(b"aad", &[0u8; 17], &[0u8; 16])
+            .unwrap_err(),
+        HPKEError::InvalidInput
+    );
+    assert_eq!(receiver_context.sequence_number(), 1);
+
+    // The single-shot API checks the length as well.
+    let (enc, ctxt) = hpke
+        .seal(&pk_r, b"info", b"aad", &[0u8; 17], None, None, None)
+        .unwrap();
+    assert_eq!(
+        hpke.open(&enc, &sk_r, b"info", b"aad", &ctxt, None, None, None)
+            .unwrap_err(),
+        HPKEError::InvalidInput
+    );
+}
+
+#[test]
+fn test_seq_bytes() {
+    let hpke = Hpke::new(
+        HpkeMode::Base,
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::ChaCha20Poly1305,
+    );
+    let (mut sender_context, mut receiver_context) = base_pair(&hpke, b"info");
+    assert_eq!(sender_context.seq_bytes(), vec![0u8; 12]);
+
+    for _ in 0..3 {
+        sender_context.seal(b"aad", b"message").unwrap();
+    }
+    let seq = sender_context.seq_bytes();
+    assert_eq!(seq, [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3]);
+
+    // The encoding is the one XORed with the base nonce.
+    let nonce: Vec<u8> = seq
+        .iter()
+        .zip(sender_context.nonce())
+        .map(|(s, n)| s ^ n)
+        .collect();
+    assert_eq!(sender_context.nonce_for_seq(3).unwrap(), nonce);
+
+    // Round trip to the receiver.
+    receiver_context.set_seq_bytes(&seq).unwrap();
+    assert_eq!(receiver_context.sequence_number(), 3);
+    assert_eq!(receiver_context.seq_bytes(), seq);
+    let ctxt = sender_context.seal(b"aad", b"message").unwrap();
+    assert_eq!(receiver_context.open(b"aad", &ctxt).unwrap(), b"message");
+
+    // Invalid encodings.
+    assert_eq!(
+        receiver_context.set_seq_bytes(&seq[1..]).unwrap_err(),
+        HPKEError::InvalidInput
+    );
+    let mut too_large = seq.clone();
+    too_large[7] = 1;
+    assert_eq!(
+        receiver_context.set_seq_bytes(&too_large).unwrap_err(),
+        HPKEError::InvalidInput
+    );
+    assert_eq!(receiver_context.sequence_number(), 4);
+}
+
+#[test]
+fn test_export_iv() {
+    let hpke = base_hpke();
+    let (sender_context, receiver_context) = base_pair(&hpke, b"info");
+
+    let ivs: Vec<Vec<u8>> = (0..8)
+        .map(|i| sender_context.export_iv(i, 16).unwrap())
+        .collect();
+    for (i, iv) in ivs.iter().enumerate() {
+        assert_eq!(iv.len(), 16);
+        assert_eq!(iv, &sender_context.export_iv(i as u64, 16).unwrap());
+        assert_eq!(iv, &receiver_context.export_iv(i as u64, 16).unwrap());
+        assert!(ivs[i + 1..].iter().all(|other| other != iv));
+    }
+    assert_ne!(ivs[0], sender_context.export_iv(u64::MAX, 16).unwrap());
+    assert_eq!(
+        sender_context.export_iv(0, 255 * 32 + 1),
+        Err(HPKEError::InvalidInput)
+    );
+}
+
+#[test]
+fn test_out_of_order_open() {
+    let hpke = Hpke::new(
+        HpkeMode::Base,
+        HpkeKemMode::DhKem25519,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::ChaCha20Poly1305,
+    );
+    let (mut sender_context, mut receiver_context) = base_pair(&hpke, b"info");
+    let ctxt_a = sender_context.seal(b"aad", b"message A").unwrap();
+    let ctxt_b = sender_context.seal(b"aad", b"message B").unwrap();
+
+    // B was sealed with the nonce for sequence number 1 and fails to open at
+    // sequence number 0. The failure doesn't advance the sequence number.
+    assert_eq!(
+        receiver_context.open(b"aad", &ctxt_b).unwrap_err(),
+        HPKEError::OpenError
+    );
+    assert_eq!(receiver_context.sequence_number(), 0);
+
+    // A opens at sequence number 0 and moves the receiver on to 1, so A can't
+    // be opened again, but B can.
+    assert_eq!(
+        receiver_context.open(b"aad", &ctxt_a).unwrap(),
+        b"message A"
+    );
+    assert_eq!(
+        receiver_context.open(b"aad", &ctxt_a).unwrap_err(),
+        HPKEError::OpenError
+    );
+    assert_eq!(
+        receiver_context.open(b"aad", &ctxt_b).unwrap(),
+        b"message B"
+    );
+
+    // Out of order messages have to be opened with an explicit sequence
+    // number, independent of the receiver's own sequence number.
+    assert_eq!(
+        receiver_context
+            .open_external_seq(1, b"aad", &ctxt_b)
+            .unwrap(),
+        b"message B"
+    );
+    assert_eq!(
+        receiver_context
+            .open_external_seq(0, b"aad", &ctxt_a)
+            .unwrap(),
+        b"message A"
+    );
+}
+
+#[test]
+fn test_seal_padded() {
+    let hpke = Hpke::new(
+        HpkeMode::Base,
+        HpkeKemMode::DhKemP256,
+        HpkeKdfMode::HkdfSha256,
+        HpkeAeadMode::AesGcm128,
+    );
+    let (mut sender_context, mut receiver_context) = base_pair(&hpke, b"info");
+
+    let short = sender_context.seal_padded(b"aad", b"yes", 64).unwrap();
+    let long = sender_context
+        .seal_padded(b"aad", b"a somewhat longer answer", 64)
+        .unwrap();
+    let empty = sender_context.seal_padded(b"aad", b"", 64).unwrap();
+    assert_eq!(short.len(), 4 + 64 + 16);
+    assert_eq!(short.len(), long.len());
+    assert_eq!(short.len(), empty.len());
+    assert_eq!(
+        receiver_context.open_padded(b"aad", &short).unwrap(),
+        b"yes"
+    );
+    assert_eq!(
+        receiver_context.open_padded(b"aad", &long).unwrap(),
+        b"a somewhat longer answer"
+    );
+    assert!(receiver_context
+        .open_padded(b"aad", &empty)
+        .unwrap()
+        .is_empty());
+
+    // Plain texts that don't fit and messages that aren't padded.
+    assert_eq!(
+        sender_context
+            .seal_padded(b"aad", &[0u8; 65], 64)
+            .unwrap_err(),
+        HPKEError::InvalidInput
+    );
+    let unpadded = sender_context.seal(b"aad", b"unpadded").unwrap();
+    assert_eq!(
+        receiver_context.open_padded(b"aad", &unpadded).unwrap_err(),
+        HPKEError::InvalidInput
+    );
+}
+
+#[test]
+fn test_max_seq() {
+    let hpke = base_hpke();
+    let (mut sender_context, mut receiver_context) = base_pair(&hpke, b"info");
+
+    // The maximum sequence number itself can't be set.
+    let mut seq = vec![0u8; 12];
+    seq[8..].copy_from_slice(&u32::MAX.to_be_bytes());
+    assert_eq!(
+        sender_context.set_seq_bytes(&seq).unwrap_err(),
+        HPKEError::InvalidInput
+    );
+
+    // The last sequence number seals and opens one more message.
+    seq[8..].copy_from_slice(&(u32::MAX - 1).to_be_bytes());
+    sender_context.set_seq_bytes(&seq).unwrap();
+    receiver_context.set_seq_bytes(&seq).unwrap();
+    let ctxt = sender_context.seal(b"aad", b"last message").unwrap();
+    assert_eq!(
+        receiver_context.open(b"aad", &ctxt).unwrap(),
+        b"last message"
+    );
+    assert_eq!(sender_context.sequence_number(), u32::MAX);
+    assert_eq!(sender_context.remaining_messages(), 0);
+
+    // After that the context is exhausted.
+    assert_eq!(
+        sender_context.seal(b"aad", b"one more").unwrap_err(),
+        HPKEError::MessageLimitReached
+    );
+    assert_eq!(
+        receiver_context.open(b"aad", &ctxt).unwrap_err(),
+        HPKEError::OpenError
+    );
+    assert_eq!(sender_context.sequence_number(), u32::MAX);
+}
@@ -139,6 +139,21 @@ fn test_kat() {
         assert_eq!(direct_ctx.exporter_secret(), exporter_secret);
         assert_eq!(direct_ctx.sequence_number(), 0);
 
+        // Build a context from the key schedule outputs.
+        let mut parts_ctx = hpke
+            .context_from_parts(key.clone(), nonce.clone(), exporter_secret.clone())
+            .unwrap();
+        let first = &test.encryptions[0];
+        let ct = parts_ctx
+            .seal(&hex_to_bytes(&first.aad), &hex_to_bytes(&first.plaintext))
+            .unwrap();
+        assert_eq!(hex_to_bytes(&first.ciphertext), ct);
+        assert_eq!(
+            hpke.context_from_parts(key[1..].to_vec(), nonce.clone(), exporter_secret.clone())
+                .err(),
+            Some(HPKEError::InvalidInput)
+        );
+
         // Test key pair derivation.
         let (my_sk_r, my_pk_r) = hpke.derive_key_pair(&ikm_r).into_keys();
         assert_eq!(sk_rm, my_sk_r);